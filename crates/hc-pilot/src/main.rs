@@ -5,6 +5,7 @@ use holochain_types::{
     dna::{AgentPubKey, AgentPubKeyB64},
 };
 use log::LevelFilter;
+use std::net::SocketAddr;
 use std::path::PathBuf;
 use std::{collections::HashMap, str::FromStr};
 use tauri::{AppHandle, Context, Wry};
@@ -31,9 +32,14 @@ struct Args {
     pub admin_port: Option<u16>,
 
     /// The agent key to install the app with.
-    #[clap(long)]
+    #[clap(long, conflicts_with = "agent_label")]
     pub agent_key: Option<String>,
 
+    /// The label of the agent key to install the app with.
+    /// If no agent key has this label yet, a new one is generated for it.
+    #[clap(long)]
+    pub agent_label: Option<String>,
+
     /// The network seed to install the app with.
     #[clap(long)]
     pub network_seed: Option<String>,
@@ -46,6 +52,11 @@ struct Args {
     #[clap(long)]
     pub bootstrap_url: Option<String>,
 
+    /// Spawn local bootstrap and signal servers listening at this address, and connect to them.
+    /// Use 0.0.0.0:<PORT> to let other devices in the local network connect to them.
+    #[clap(long, conflicts_with_all = ["signal_url", "bootstrap_url"])]
+    pub local_servers: Option<SocketAddr>,
+
     /// The directory where the conductor directories will be created.
     /// By default a new folder in the /tmp directory.
     #[clap(long)]
@@ -97,6 +108,15 @@ fn main() {
         }
     };
 
+    let mut config =
+        HolochainPluginConfig::new(conductor_dir, network_config).enable_mdns_discovery();
+    if let Some(admin_port) = args.admin_port {
+        config = config.admin_port(admin_port);
+    }
+    if let Some(local_servers) = args.local_servers {
+        config = config.spawn_local_servers(local_servers);
+    }
+
     tauri::Builder::default()
         .plugin(
             tauri_plugin_log::Builder::default()
@@ -108,23 +128,19 @@ fn main() {
         )
         .plugin(tauri_plugin_holochain::init(
             vec_to_locked(password.as_bytes().to_vec()),
-            HolochainPluginConfig {
-                network_config,
-                holochain_dir: conductor_dir,
-                admin_port: args.admin_port,
-                mdns_discovery: true,
-            },
+            config,
         ))
         .setup(|app| {
-            let agent_key = match args.agent_key {
-                Some(key) => {
-                    let key_b64 = AgentPubKeyB64::from_b64_str(key.as_str())?;
-                    Some(AgentPubKey::from(key_b64))
-                }
-                None => None,
-            };
             let handle = app.handle();
             let result: anyhow::Result<()> = tauri::async_runtime::block_on(async move {
+                let agent_key = match (args.agent_key, args.agent_label) {
+                    (Some(key), _) => {
+                        let key_b64 = AgentPubKeyB64::from_b64_str(key.as_str())?;
+                        Some(AgentPubKey::from(key_b64))
+                    }
+                    (None, Some(label)) => Some(agent_key_with_label(handle.clone(), label).await?),
+                    (None, None) => None,
+                };
                 let app_info = setup(
                     handle.clone(),
                     args.happ_bundle_path,
@@ -155,6 +171,20 @@ fn main() {
         .expect("error while running tauri application");
 }
 
+async fn agent_key_with_label(handle: AppHandle, label: String) -> anyhow::Result<AgentPubKey> {
+    let holochain_runtime = &handle.holochain()?.holochain_runtime;
+    let agent_keys = holochain_runtime.list_agent_keys().await?;
+
+    let existing_key = agent_keys
+        .into_iter()
+        .find(|agent_key| agent_key.label.as_ref().eq(&Some(&label)));
+
+    match existing_key {
+        Some(agent_key) => Ok(agent_key.agent_pub_key),
+        None => Ok(holochain_runtime.generate_agent_key(Some(label)).await?),
+    }
+}
+
 async fn setup(
     handle: AppHandle,
     app_bundle_path: PathBuf,
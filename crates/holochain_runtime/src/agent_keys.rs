@@ -0,0 +1,146 @@
+use std::collections::BTreeMap;
+
+use holochain_client::{AgentPubKey, InstalledAppId};
+use holochain_types::prelude::AgentPubKeyB64;
+use lair_keystore_api::prelude::LairEntryInfo;
+use serde::{Deserialize, Serialize};
+
+use crate::{filesystem::AgentKeyInfo, launch::DEVICE_SEED_LAIR_KEYSTORE_TAG, HolochainRuntime};
+
+/// An agent key, with the apps that were installed with it
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AgentKey {
+    pub agent_pub_key: AgentPubKey,
+    /// Human readable label for the key, set with `HolochainRuntime::set_agent_key_label()`
+    pub label: Option<String>,
+    /// Index of the path from which the key was derived from the device seed,
+    /// `None` if the key was not generated by `HolochainRuntime::generate_agent_key()`
+    pub derivation_index: Option<u32>,
+    pub installed_app_ids: Vec<InstalledAppId>,
+}
+
+fn agent_key_lair_tag(derivation_index: u32) -> String {
+    format!("{DEVICE_SEED_LAIR_KEYSTORE_TAG}.AGENT_KEY.{derivation_index}")
+}
+
+fn agent_key_error(reason: String) -> crate::Error {
+    crate::Error::AgentKeyError(reason)
+}
+
+impl HolochainRuntime {
+    /// Generates a new agent key, derived from the device seed
+    /// Keys are derived in order, so restoring the device seed with `HolochainRuntimeConfig::restore_device_seed_backup()`
+    /// and generating the same number of keys gives back the same agent keys
+    ///
+    /// * `label` - human readable label for the key, e.g. the name of the profile that will use it
+    pub async fn generate_agent_key(&self, label: Option<String>) -> crate::Result<AgentPubKey> {
+        let agent_keys = self.filesystem.bundle_store.agent_keys_store.get()?;
+        let derivation_index = agent_keys
+            .values()
+            .filter_map(|info| info.derivation_index)
+            .max()
+            .map(|index| index + 1)
+            .unwrap_or(0);
+
+        let lair_client = self.conductor_launcher.keystore().await.lair_client();
+        let tag = agent_key_lair_tag(derivation_index);
+
+        // The key may already be in lair if it was derived before its info could be stored
+        let ed25519_pub_key = match lair_client.get_entry(tag.clone().into()).await {
+            Ok(LairEntryInfo::Seed { seed_info, .. }) => seed_info.ed25519_pub_key,
+            Ok(_) => {
+                return Err(agent_key_error(format!("Lair entry {tag} is not a seed")));
+            }
+            Err(_) => {
+                lair_client
+                    .derive_seed(
+                        DEVICE_SEED_LAIR_KEYSTORE_TAG.into(),
+                        None,
+                        tag.into(),
+                        None,
+                        vec![derivation_index].into_boxed_slice(),
+                    )
+                    .await
+                    .map_err(|err| crate::Error::LairError(err))?
+                    .ed25519_pub_key
+            }
+        };
+        let agent_pub_key = AgentPubKey::from_raw_32(ed25519_pub_key.to_vec());
+
+        self.filesystem
+            .bundle_store
+            .agent_keys_store
+            .update(|agent_keys| {
+                agent_keys.insert(
+                    agent_pub_key.to_string(),
+                    AgentKeyInfo {
+                        label: label.clone(),
+                        derivation_index: Some(derivation_index),
+                    },
+                );
+            })?;
+
+        log::info!("Generated agent key {agent_pub_key} with derivation index {derivation_index}.");
+
+        Ok(agent_pub_key)
+    }
+
+    /// Lists all the agent keys generated with `Self::generate_agent_key()` or used by any installed app,
+    /// with the apps that were installed with each of them
+    pub async fn list_agent_keys(&self) -> crate::Result<Vec<AgentKey>> {
+        let admin_ws = self.admin_websocket().await?;
+        let apps = admin_ws.list_apps(None).await?;
+        let agent_keys_info = self.filesystem.bundle_store.agent_keys_store.get()?;
+
+        let mut agent_keys: BTreeMap<String, AgentKey> = BTreeMap::new();
+
+        for (agent_pub_key, info) in agent_keys_info {
+            let agent_pub_key_b64 = AgentPubKeyB64::from_b64_str(&agent_pub_key)
+                .map_err(|err| agent_key_error(format!("{err:?}")))?;
+            agent_keys.insert(
+                agent_pub_key,
+                AgentKey {
+                    agent_pub_key: agent_pub_key_b64.into(),
+                    label: info.label,
+                    derivation_index: info.derivation_index,
+                    installed_app_ids: vec![],
+                },
+            );
+        }
+
+        for app in apps {
+            agent_keys
+                .entry(app.agent_pub_key.to_string())
+                .or_insert_with(|| AgentKey {
+                    agent_pub_key: app.agent_pub_key.clone(),
+                    label: None,
+                    derivation_index: None,
+                    installed_app_ids: vec![],
+                })
+                .installed_app_ids
+                .push(app.installed_app_id);
+        }
+
+        Ok(agent_keys.into_values().collect())
+    }
+
+    /// Sets the human readable label for the given agent key
+    ///
+    /// * `agent_pub_key` - the agent key to label
+    /// * `label` - the new label, or `None` to remove it
+    pub async fn set_agent_key_label(
+        &self,
+        agent_pub_key: AgentPubKey,
+        label: Option<String>,
+    ) -> crate::Result<()> {
+        self.filesystem
+            .bundle_store
+            .agent_keys_store
+            .update(|agent_keys| {
+                agent_keys
+                    .entry(agent_pub_key.to_string())
+                    .or_default()
+                    .label = label.clone();
+            })
+    }
+}
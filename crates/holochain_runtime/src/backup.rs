@@ -0,0 +1,330 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use zip::{write::FileOptions, CompressionMethod, ZipArchive, ZipWriter};
+
+use crate::filesystem::FileSystemError;
+
+const BACKUP_VERSION: u32 = 1;
+const BACKUP_MANIFEST_FILE: &'static str = "holochain-backup.json";
+
+/// Describes the contents of a backup archive
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BackupManifest {
+    pub version: u32,
+    /// Seconds since the UNIX epoch at which the backup was created
+    pub created_at: u64,
+    /// The SHA-256 hash of every file in the backup, by its path relative to the holochain directory
+    pub files: BTreeMap<String, String>,
+}
+
+fn backup_error(reason: String) -> crate::Error {
+    crate::Error::BackupError(reason)
+}
+
+/// Computes the SHA-256 hash of everything written through it
+pub(crate) struct HashingWriter<W: Write> {
+    inner: W,
+    hasher: Sha256,
+}
+
+impl<W: Write> HashingWriter<W> {
+    pub(crate) fn new(inner: W) -> Self {
+        Self {
+            inner,
+            hasher: Sha256::new(),
+        }
+    }
+
+    /// The hex encoded hash of what was written, in the same format as `sha256::digest()`
+    pub(crate) fn finalize(self) -> String {
+        format!("{:x}", self.hasher.finalize())
+    }
+}
+
+impl<W: Write> Write for HashingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.hasher.update(&buf[..written]);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+fn list_files(dir: &Path, files: &mut Vec<PathBuf>) -> crate::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            list_files(&path, files)?;
+        } else if path.is_file() {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Paths inside the archive always use '/', regardless of the platform
+fn archive_path(holochain_dir: &Path, file: &Path) -> crate::Result<String> {
+    let relative_path = file
+        .strip_prefix(holochain_dir)
+        .map_err(|err| backup_error(format!("{err:?}")))?;
+    let components: Vec<String> = relative_path
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy().to_string())
+        .collect();
+    Ok(components.join("/"))
+}
+
+/// Fails if `backup_path` is inside `holochain_dir`, where the previous backups would be archived into the new one
+pub(crate) fn check_backup_path(holochain_dir: &Path, backup_path: &Path) -> crate::Result<()> {
+    let holochain_dir = holochain_dir.canonicalize()?;
+    let backup_dir = match backup_path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let backup_dir = match backup_dir.canonicalize() {
+        Ok(backup_dir) => backup_dir,
+        Err(_) => std::path::absolute(backup_dir)?,
+    };
+
+    if backup_dir.starts_with(&holochain_dir) {
+        return Err(backup_error(format!(
+            "Can't create the backup {backup_path:?} inside the holochain directory {holochain_dir:?}"
+        )));
+    }
+    Ok(())
+}
+
+/// Writes all the files in `holochain_dir` to a zip archive at `backup_path`
+/// Nothing must be writing to `holochain_dir` meanwhile, since each file is hashed while it's copied to the archive
+pub(crate) fn write_backup_archive(holochain_dir: &Path, backup_path: &Path) -> crate::Result<()> {
+    let mut files = Vec::new();
+    list_files(holochain_dir, &mut files)?;
+
+    let mut manifest = BackupManifest {
+        version: BACKUP_VERSION,
+        created_at: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_err(|err| backup_error(format!("{err:?}")))?
+            .as_secs(),
+        files: BTreeMap::new(),
+    };
+
+    let tmp_backup_path = backup_path.with_extension("tmp");
+    let mut zip = ZipWriter::new(fs::File::create(&tmp_backup_path)?);
+    let options = FileOptions::default()
+        .compression_method(CompressionMethod::Deflated)
+        .large_file(true);
+
+    for file in files {
+        let path = archive_path(holochain_dir, &file)?;
+
+        zip.start_file(path.clone(), options)
+            .map_err(FileSystemError::from)?;
+        let mut writer = HashingWriter::new(&mut zip);
+        std::io::copy(&mut fs::File::open(&file)?, &mut writer)?;
+        let hash = writer.finalize();
+
+        manifest.files.insert(path, hash);
+    }
+
+    zip.start_file(BACKUP_MANIFEST_FILE, options)
+        .map_err(FileSystemError::from)?;
+    zip.write_all(serde_json::to_string_pretty(&manifest)?.as_bytes())?;
+    zip.finish().map_err(FileSystemError::from)?;
+
+    fs::rename(tmp_backup_path, backup_path)?;
+
+    log::info!(
+        "Created backup with {} files at {backup_path:?}.",
+        manifest.files.len()
+    );
+
+    Ok(())
+}
+
+fn has_files(dir: &Path) -> crate::Result<bool> {
+    if !dir.exists() {
+        return Ok(false);
+    }
+    let mut files = Vec::new();
+    list_files(dir, &mut files)?;
+    Ok(!files.is_empty())
+}
+
+/// Extracts the backup archive at `backup_path` into `holochain_dir`, checking the integrity of every file
+/// `holochain_dir` must not contain any files yet
+pub(crate) fn restore_backup_archive(
+    backup_path: &Path,
+    holochain_dir: &Path,
+) -> crate::Result<BackupManifest> {
+    if has_files(holochain_dir)? {
+        return Err(backup_error(format!(
+            "Can't restore a backup into {holochain_dir:?}: it already contains files"
+        )));
+    }
+
+    let mut archive =
+        ZipArchive::new(fs::File::open(backup_path)?).map_err(FileSystemError::from)?;
+
+    let manifest: BackupManifest = {
+        let manifest_file = archive
+            .by_name(BACKUP_MANIFEST_FILE)
+            .map_err(|_err| backup_error(String::from("The archive is not a holochain backup")))?;
+        serde_json::from_reader(manifest_file)?
+    };
+    if manifest.version != BACKUP_VERSION {
+        return Err(backup_error(format!(
+            "Unsupported backup version {}",
+            manifest.version
+        )));
+    }
+
+    // Extract next to the final directory and move it in place only after all the checks have passed
+    let restoring_dir = holochain_dir.with_extension("restoring");
+    if restoring_dir.exists() {
+        fs::remove_dir_all(&restoring_dir)?;
+    }
+
+    let result = extract_files(&mut archive, &manifest, &restoring_dir);
+    if let Err(err) = result {
+        fs::remove_dir_all(&restoring_dir)?;
+        return Err(err);
+    }
+
+    if holochain_dir.exists() {
+        fs::remove_dir_all(holochain_dir)?;
+    }
+    fs::rename(&restoring_dir, holochain_dir)?;
+
+    log::info!(
+        "Restored backup with {} files into {holochain_dir:?}.",
+        manifest.files.len()
+    );
+
+    Ok(manifest)
+}
+
+fn extract_files(
+    archive: &mut ZipArchive<fs::File>,
+    manifest: &BackupManifest,
+    outdir: &Path,
+) -> crate::Result<()> {
+    for (path, expected_hash) in manifest.files.iter() {
+        let mut file = archive
+            .by_name(path)
+            .map_err(|_err| backup_error(format!("File {path} is missing from the backup")))?;
+        let Some(relative_path) = file.enclosed_name().map(|p| p.to_path_buf()) else {
+            return Err(backup_error(format!("Invalid file path in backup: {path}")));
+        };
+        let outpath = outdir.join(relative_path);
+
+        if let Some(parent) = outpath.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut outfile = fs::File::create(&outpath)?;
+        let mut writer = HashingWriter::new(&mut outfile);
+        std::io::copy(&mut file, &mut writer)?;
+        let hash = writer.finalize();
+        outfile.flush()?;
+
+        if hash.ne(expected_hash) {
+            return Err(backup_error(format!(
+                "Integrity check failed for file {path}"
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn holochain_dir() -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("conductor")).unwrap();
+        fs::write(dir.path().join("conductor").join("db"), b"conductor db").unwrap();
+        fs::write(dir.path().join("installed-apps.json"), b"{}").unwrap();
+        dir
+    }
+
+    #[test]
+    fn restores_the_files_of_the_backup() {
+        let holochain_dir = holochain_dir();
+        let backups_dir = tempfile::tempdir().unwrap();
+        let backup_path = backups_dir.path().join("backup.zip");
+        write_backup_archive(holochain_dir.path(), &backup_path).unwrap();
+
+        let restored_dir = backups_dir.path().join("restored");
+        let manifest = restore_backup_archive(&backup_path, &restored_dir).unwrap();
+
+        assert_eq!(
+            manifest.files.keys().collect::<Vec<_>>(),
+            vec!["conductor/db", "installed-apps.json"]
+        );
+        assert_eq!(
+            fs::read(restored_dir.join("conductor").join("db")).unwrap(),
+            b"conductor db"
+        );
+        assert_eq!(
+            fs::read(restored_dir.join("installed-apps.json")).unwrap(),
+            b"{}"
+        );
+    }
+
+    #[test]
+    fn rejects_backups_with_tampered_files() {
+        let holochain_dir = holochain_dir();
+        let backups_dir = tempfile::tempdir().unwrap();
+        let backup_path = backups_dir.path().join("backup.zip");
+        write_backup_archive(holochain_dir.path(), &backup_path).unwrap();
+
+        // Copy the backup, replacing the contents of one file but keeping the manifest
+        let tampered_backup_path = backups_dir.path().join("tampered.zip");
+        let mut archive = ZipArchive::new(fs::File::open(&backup_path).unwrap()).unwrap();
+        let mut zip = ZipWriter::new(fs::File::create(&tampered_backup_path).unwrap());
+        for index in 0..archive.len() {
+            let mut file = archive.by_index(index).unwrap();
+            zip.start_file(file.name(), FileOptions::default()).unwrap();
+            if file.name().eq("conductor/db") {
+                zip.write_all(b"tampered db").unwrap();
+            } else {
+                std::io::copy(&mut file, &mut zip).unwrap();
+            }
+        }
+        zip.finish().unwrap();
+
+        let restored_dir = backups_dir.path().join("restored");
+        let result = restore_backup_archive(&tampered_backup_path, &restored_dir);
+
+        assert!(matches!(result, Err(crate::Error::BackupError(_))));
+        assert!(!restored_dir.exists());
+    }
+
+    #[test]
+    fn rejects_backups_inside_the_holochain_dir() {
+        let holochain_dir = holochain_dir();
+        let backups_dir = tempfile::tempdir().unwrap();
+
+        assert!(check_backup_path(
+            holochain_dir.path(),
+            &holochain_dir.path().join("conductor").join("backup.zip")
+        )
+        .is_err());
+        assert!(
+            check_backup_path(holochain_dir.path(), &backups_dir.path().join("backup.zip")).is_ok()
+        );
+    }
+}
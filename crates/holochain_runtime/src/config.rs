@@ -1,19 +1,64 @@
 use holochain_conductor_api::conductor::NetworkConfig;
+use lair_keystore_api::types::SharedLockedArray;
+#[cfg(feature = "local-servers")]
+use std::net::SocketAddr;
+#[cfg(feature = "local-servers")]
+use crate::LocalServersMode;
 use std::path::PathBuf;
+use std::time::Duration;
 
+use crate::{NetworkProfile, DEFAULT_SHUTDOWN_TIMEOUT};
+
+#[derive(Clone)]
 pub struct HolochainRuntimeConfig {
     /// The directory where the holochain files and databases will be stored in
     pub holochain_dir: PathBuf,
 
     // Holochain network config
+    // Used as is with the `NetworkProfile::Wan` profile
     pub network_config: NetworkConfig,
 
+    /// The network profile to launch with
+    /// Only applies until the profile is changed with `HolochainRuntime::set_network_profile()`
+    pub network_profile: NetworkProfile,
+
     /// Force the conductor to run at this admin port
     pub admin_port: Option<u16>,
 
     /// Enable mDNS based discovery
     /// Useful to discover peers in the same LAN
-    pub mdns_discovery: bool
+    /// Only applies until the setting is changed with `HolochainRuntime::set_mdns_discovery_enabled()`
+    pub mdns_discovery: bool,
+
+    /// Enable DPKI with the given DeepKey DNA
+    /// Agent keys will be derived from the device seed and registered in DeepKey
+    #[cfg(feature = "dpki")]
+    pub dpki: Option<DpkiRuntimeConfig>,
+
+    /// Spawn in-process bootstrap and signal servers listening at this address,
+    /// and use them instead of the ones in `network_config`
+    #[cfg(feature = "local-servers")]
+    pub local_servers: Option<SocketAddr>,
+
+    /// The defaults of the bootstrap server used by the local servers
+    #[cfg(feature = "local-servers")]
+    pub local_servers_mode: LocalServersMode,
+
+    /// Restore the device seed from a backup exported with `HolochainRuntime::export_device_seed_backup()`
+    /// Only applies to fresh installs: if the keystore already has a device seed, it must be the one in the backup
+    pub device_seed_backup: Option<DeviceSeedBackupConfig>,
+
+    /// How long `HolochainRuntime::shutdown()` can take before giving up
+    pub shutdown_timeout: Duration,
+}
+
+#[derive(Clone)]
+pub struct DeviceSeedBackupConfig {
+    /// The backup exported with `HolochainRuntime::export_device_seed_backup()`
+    pub backup: Vec<u8>,
+
+    /// The passphrase the backup was encrypted with
+    pub passphrase: SharedLockedArray,
 }
 
 impl HolochainRuntimeConfig {
@@ -21,8 +66,17 @@ impl HolochainRuntimeConfig {
         Self {
             holochain_dir,
             network_config,
+            network_profile: NetworkProfile::default(),
             admin_port: None,
-            mdns_discovery: false
+            mdns_discovery: false,
+            #[cfg(feature = "dpki")]
+            dpki: None,
+            #[cfg(feature = "local-servers")]
+            local_servers: None,
+            #[cfg(feature = "local-servers")]
+            local_servers_mode: LocalServersMode::default(),
+            device_seed_backup: None,
+            shutdown_timeout: DEFAULT_SHUTDOWN_TIMEOUT,
         }
     }
 
@@ -31,8 +85,83 @@ impl HolochainRuntimeConfig {
         self
     }
 
+    pub fn network_profile(mut self, network_profile: NetworkProfile) -> Self {
+        self.network_profile = network_profile;
+        self
+    }
+
     pub fn enable_mdns_discovery(mut self) -> Self {
         self.mdns_discovery = true;
         self
     }
+
+    pub fn restore_device_seed_backup(
+        mut self,
+        backup: Vec<u8>,
+        passphrase: SharedLockedArray,
+    ) -> Self {
+        self.device_seed_backup = Some(DeviceSeedBackupConfig { backup, passphrase });
+        self
+    }
+
+    pub fn shutdown_timeout(mut self, shutdown_timeout: Duration) -> Self {
+        self.shutdown_timeout = shutdown_timeout;
+        self
+    }
+
+    /// Spawns in-process bootstrap and signal servers, so that peers can connect without any external infrastructure
+    /// Other devices in the local network can connect to them using `HolochainRuntime::local_servers`'s URLs
+    ///
+    /// * `listen_address` - the address to bind to; use port 0 to pick a free port,
+    ///    and `0.0.0.0` to accept connections from other devices in the local network
+    #[cfg(feature = "local-servers")]
+    pub fn spawn_local_servers(mut self, listen_address: SocketAddr) -> Self {
+        self.local_servers = Some(listen_address);
+        self
+    }
+
+    /// Runs the local servers spawned with `Self::spawn_local_servers()` with the defaults for the given mode,
+    /// `LocalServersMode::Production` by default
+    #[cfg(feature = "local-servers")]
+    pub fn local_servers_mode(mut self, local_servers_mode: LocalServersMode) -> Self {
+        self.local_servers_mode = local_servers_mode;
+        self
+    }
+
+    #[cfg(feature = "dpki")]
+    pub fn enable_dpki(mut self, dpki: DpkiRuntimeConfig) -> Self {
+        self.dpki = Some(dpki);
+        self
+    }
+}
+
+/// The DeepKey DNA to run as the DPKI service
+#[cfg(feature = "dpki")]
+#[derive(Clone, Debug)]
+pub enum DeepkeyDna {
+    /// The DeepKey DNA that is bundled with holochain
+    Bundled,
+    /// A DeepKey DNA bundle found at the given path
+    Path(PathBuf),
+}
+
+#[cfg(feature = "dpki")]
+#[derive(Clone, Debug)]
+pub struct DpkiRuntimeConfig {
+    /// The DeepKey DNA to install
+    pub deepkey_dna: DeepkeyDna,
+
+    /// The network seed for the DeepKey DNA
+    /// All the agents that need to verify each other's keys must use the same one
+    pub network_seed: String,
+}
+
+#[cfg(feature = "dpki")]
+impl DpkiRuntimeConfig {
+    pub fn new(deepkey_dna: DeepkeyDna, network_seed: String) -> Self {
+        Self {
+            deepkey_dna,
+            network_seed,
+        }
+    }
 }
@@ -0,0 +1,85 @@
+use holochain::prelude::Timestamp;
+use holochain_client::{AgentPubKey, InstalledAppId};
+use holochain_types::dpki::KeyState;
+use serde::{Deserialize, Serialize};
+
+use crate::HolochainRuntime;
+
+/// The state of an agent key as registered in DeepKey
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub enum AgentKeyState {
+    /// The key was never registered in DeepKey
+    NotFound,
+    /// The key is registered and has not been revoked
+    Valid,
+    /// The key was registered but has since been revoked or updated
+    Invalid,
+}
+
+impl From<KeyState> for AgentKeyState {
+    fn from(key_state: KeyState) -> Self {
+        match key_state {
+            KeyState::NotFound => AgentKeyState::NotFound,
+            KeyState::Valid(_) => AgentKeyState::Valid,
+            KeyState::Invalid(_) => AgentKeyState::Invalid,
+        }
+    }
+}
+
+impl HolochainRuntime {
+    /// Whether the conductor is running with the DPKI service
+    pub async fn is_dpki_enabled(&self) -> bool {
+        self.conductor_handle().await.running_services().dpki.is_some()
+    }
+
+    /// Get the current DeepKey registration state for the given agent key
+    ///
+    /// * `agent_key` - the agent key to query
+    pub async fn agent_key_state(&self, agent_key: AgentPubKey) -> crate::Result<AgentKeyState> {
+        let dpki = self
+            .conductor_handle()
+            .await
+            .running_services()
+            .dpki
+            .ok_or(crate::Error::DpkiNotEnabled)?;
+
+        let key_state = dpki
+            .state()
+            .await
+            .key_state(agent_key, Timestamp::now())
+            .await
+            .map_err(|err| crate::Error::DpkiError(format!("{err:?}")))?;
+
+        Ok(key_state.into())
+    }
+
+    /// Revoke the given agent key in DeepKey, and disable all the cells of the given app that were using it
+    /// The key can't be used to author any more actions after this, so this can't be undone
+    ///
+    /// To rotate the key for an app, revoke it and install the app again with a new agent key
+    ///
+    /// * `app_id` - the app that is using the agent key
+    /// * `agent_key` - the agent key to revoke
+    pub async fn revoke_agent_key(
+        &self,
+        app_id: InstalledAppId,
+        agent_key: AgentPubKey,
+    ) -> crate::Result<()> {
+        if !self.is_dpki_enabled().await {
+            return Err(crate::Error::DpkiNotEnabled);
+        }
+
+        let admin_ws = self.admin_websocket().await?;
+        let errors = admin_ws.revoke_agent_key(app_id.clone(), agent_key).await?;
+
+        if !errors.is_empty() {
+            return Err(crate::Error::DpkiError(format!(
+                "Failed to revoke the agent key for some cells of app {app_id}: {errors:?}"
+            )));
+        }
+
+        log::info!("Revoked agent key for app {app_id}.");
+
+        Ok(())
+    }
+}
@@ -16,6 +16,30 @@ pub enum Error {
     #[error(transparent)]
     LairError(OneErr),
 
+    #[error("Wrong passphrase")]
+    WrongPassphrase,
+
+    #[error("Passphrase error: {0}")]
+    PassphraseError(String),
+
+    #[error("Device seed backup error: {0}")]
+    DeviceSeedBackupError(String),
+
+    #[error("Backup error: {0}")]
+    BackupError(String),
+
+    #[error("Agent key error: {0}")]
+    AgentKeyError(String),
+
+    #[error("Peer info error: {0}")]
+    PeerInfoError(String),
+
+    #[error("Local servers error: {0}")]
+    LocalServersError(String),
+
+    #[error("Testing error: {0}")]
+    TestingError(String),
+
     #[error(transparent)]
     ConductorError(#[from] ConductorError),
 
@@ -66,6 +90,21 @@ pub enum Error {
 
     #[error("Error shutting down holochain: {0}")]
     HolochainShutdownError(String),
+
+    #[error("Shutdown timed out in phase {0}")]
+    ShutdownTimeout(String),
+
+    #[error("The holochain runtime is shutting down")]
+    ShuttingDown,
+
+    #[error("Supervisor error: {0}")]
+    SupervisorError(String),
+
+    #[error("DPKI is not enabled in this conductor")]
+    DpkiNotEnabled,
+
+    #[error("DPKI error: {0}")]
+    DpkiError(String),
 }
 
 impl Serialize for Error {
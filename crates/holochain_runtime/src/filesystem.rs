@@ -1,18 +1,32 @@
-use std::collections::HashMap;
-use std::path::PathBuf;
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
+use std::io::{self, Cursor, Read, Seek, SeekFrom};
+use std::path::{Component, Path, PathBuf};
 use std::sync::Arc;
-use std::sync::RwLock;
+use std::sync::{Mutex, MutexGuard, RwLock};
 use std::{fs, io::Write};
 
+use flate2::read::DeflateDecoder;
+
 use holochain::prelude::*;
 use holochain_types::web_app::WebAppBundle;
 use mr_bundle::error::MrBundleError;
-use zip::result::ZipError;
+use serde::de::DeserializeOwned;
+use zip::{result::ZipError, CompressionMethod, ZipArchive};
+
+use crate::backup::HashingWriter;
+use crate::{MdnsDiscoverySettings, NetworkProfile};
 
 #[derive(Clone)]
 pub struct FileSystem {
     pub app_data_dir: PathBuf,
     pub bundle_store: BundleStore,
+    /// `None` until the settings are changed at runtime, in which case they override `HolochainRuntimeConfig::mdns_discovery`
+    pub mdns_discovery_settings_store: JsonStore<Option<MdnsDiscoverySettings>>,
+    /// `None` until the profile is changed at runtime, in which case it overrides `HolochainRuntimeConfig::network_profile`
+    pub network_profile_store: JsonStore<Option<NetworkProfile>>,
+    /// The apps that the runtime itself disabled, e.g. to leave their networks in shutdown,
+    /// which are enabled again in the next launch, unlike the apps disabled by the user
+    pub apps_disabled_by_runtime_store: JsonStore<BTreeSet<InstalledAppId>>,
 }
 
 impl FileSystem {
@@ -20,10 +34,18 @@ impl FileSystem {
         let bundle_store_path = app_data_dir.join("bundles");
         fs::create_dir_all(bundle_store_path.clone())?;
         let bundle_store = BundleStore::new(bundle_store_path)?;
+        let mdns_discovery_settings_store =
+            JsonStore::new(app_data_dir.join("mdns-discovery-settings.json"))?;
+        let network_profile_store = JsonStore::new(app_data_dir.join("network-profile.json"))?;
+        let apps_disabled_by_runtime_store =
+            JsonStore::new(app_data_dir.join("apps-disabled-by-runtime.json"))?;
 
         let fs = FileSystem {
             app_data_dir,
             bundle_store,
+            mdns_discovery_settings_store,
+            network_profile_store,
+            apps_disabled_by_runtime_store,
         };
 
         fs::create_dir_all(fs.keystore_dir())?;
@@ -40,6 +62,10 @@ impl FileSystem {
         self.keystore_dir().join("lair-keystore-config.yaml")
     }
 
+    pub fn keystore_passphrase_path(&self) -> PathBuf {
+        self.keystore_dir().join("passphrase.json")
+    }
+
     pub fn keystore_store_path(&self) -> PathBuf {
         self.keystore_dir().join("store_file")
     }
@@ -53,16 +79,59 @@ impl FileSystem {
 pub struct BundleStore {
     path: PathBuf,
     pub installed_apps_store: InstalledAppsStore,
+    pub agent_keys_store: AgentKeysStore,
+    /// In-memory index of the installed apps by their lowercased app id,
+    /// so that serving UI assets doesn't read the whole `installed_apps_store`
+    ui_index: Arc<RwLock<HashMap<String, IndexedApp>>>,
+    /// The UI archives already loaded and verified, by their hash
+    ui_archives: Arc<RwLock<HashMap<String, Arc<UiArchive>>>>,
+    /// The compressed files of the UI archives that were decompressed to serve range requests
+    decompressed_ui_files: Arc<Mutex<DecompressedUiFiles>>,
+}
+
+#[derive(Debug, Clone)]
+struct IndexedApp {
+    app_id: InstalledAppId,
+    ui_hash: Option<String>,
+}
+
+/// The UI of an installed app, as returned by `BundleStore::get_ui()`
+#[derive(Clone)]
+pub struct InstalledUi {
+    /// The app id with its original casing
+    pub app_id: InstalledAppId,
+    pub ui_hash: String,
+    pub content: UiContent,
+}
+
+/// Where the files of a UI are stored
+#[derive(Clone)]
+pub enum UiContent {
+    /// The zip archive of the UI, from which its files are served without extracting them
+    Archive(Arc<UiArchive>),
+    /// The directory where the UI was extracted, for UIs installed before they were stored as archives
+    Directory(PathBuf),
 }
 
 impl BundleStore {
     fn new(path: PathBuf) -> crate::Result<Self> {
         let installed_apps_store = InstalledAppsStore::new(path.join("installed-apps.json"))?;
+        let agent_keys_store = AgentKeysStore::new(path.join("agent-keys.json"))?;
 
         let bundle_store = BundleStore {
             path,
             installed_apps_store,
+            agent_keys_store,
+            ui_index: Arc::new(RwLock::new(HashMap::new())),
+            ui_archives: Arc::new(RwLock::new(HashMap::new())),
+            decompressed_ui_files: Arc::new(Mutex::new(DecompressedUiFiles::default())),
         };
+        for (app_id, installed_app_info) in bundle_store.installed_apps_store.get()? {
+            let ui_hash = installed_app_info
+                .web_app_info
+                .map(|web_app_info| web_app_info.ui_hash);
+            bundle_store.index_app(app_id, ui_hash)?;
+        }
         fs::create_dir_all(bundle_store.happ_bundle_store().path)?;
         fs::create_dir_all(bundle_store.ui_store().path)?;
 
@@ -81,21 +150,174 @@ impl BundleStore {
         }
     }
 
-    pub fn get_ui_path(&self, app_id: &InstalledAppId) -> crate::Result<PathBuf> {
-        let installed_apps = self.installed_apps_store.get()?;
+    fn index_app(&self, app_id: InstalledAppId, ui_hash: Option<String>) -> crate::Result<()> {
+        let mut ui_index = self
+            .ui_index
+            .write()
+            .map_err(|err| crate::Error::LockError(format!("{err:?}")))?;
+        let key = app_id.to_lowercase();
+        if let Some(indexed_app) = ui_index.get(&key) {
+            if indexed_app.app_id.ne(&app_id) {
+                log::warn!(
+                    "Apps {} and {app_id} only differ in casing: the UI for {app_id} will be served for both.",
+                    indexed_app.app_id
+                );
+            }
+        }
+        ui_index.insert(key, IndexedApp { app_id, ui_hash });
+
+        // Unload the archives of the UIs that were replaced
+        self.release_unused_ui_archives(&ui_index)
+    }
+
+    /// Forgets the given app after it was uninstalled, releasing and deleting the archive of its UI if no other app uses it
+    pub fn remove_app(&self, app_id: &InstalledAppId) -> crate::Result<()> {
+        let ui_hash = self.installed_ui_hash(app_id)?;
+        self.installed_apps_store.update(|installed_apps| {
+            installed_apps.remove(app_id);
+        })?;
+
+        let mut ui_index = self
+            .ui_index
+            .write()
+            .map_err(|err| crate::Error::LockError(format!("{err:?}")))?;
+        let key = app_id.to_lowercase();
+        if ui_index
+            .get(&key)
+            .is_some_and(|indexed_app| indexed_app.app_id.eq(app_id))
+        {
+            ui_index.remove(&key);
+        }
+        self.release_unused_ui_archives(&ui_index)?;
+        drop(ui_index);
+
+        if let Some(ui_hash) = ui_hash {
+            self.delete_ui_if_unused(&ui_hash)?;
+        }
+        Ok(())
+    }
+
+    fn installed_ui_hash(&self, app_id: &InstalledAppId) -> crate::Result<Option<String>> {
+        Ok(self
+            .installed_apps_store
+            .get()?
+            .get(app_id)
+            .and_then(|installed_app_info| installed_app_info.web_app_info.as_ref())
+            .map(|web_app_info| web_app_info.ui_hash.clone()))
+    }
+
+    /// Deletes the stored UI with the given hash if no installed app uses it anymore
+    fn delete_ui_if_unused(&self, ui_hash: &String) -> crate::Result<()> {
+        let in_use = self
+            .installed_apps_store
+            .get()?
+            .values()
+            .filter_map(|installed_app_info| installed_app_info.web_app_info.as_ref())
+            .any(|web_app_info| web_app_info.ui_hash.eq(ui_hash));
+        if in_use {
+            return Ok(());
+        }
+
+        let ui_store = self.ui_store();
+        let archive_path = ui_store.get_archive_path_for_ui_with_hash(ui_hash);
+        if archive_path.exists() {
+            fs::remove_file(&archive_path)?;
+        }
+        let ui_dir = ui_store.get_path_for_ui_with_hash(ui_hash);
+        if ui_dir.exists() {
+            fs::remove_dir_all(&ui_dir)?;
+        }
+        log::info!("Deleted the UI {ui_hash}, which no installed app uses anymore.");
+
+        Ok(())
+    }
+
+    /// Drops the archives and the decompressed files of the UIs that no indexed app uses anymore
+    fn release_unused_ui_archives(
+        &self,
+        ui_index: &HashMap<String, IndexedApp>,
+    ) -> crate::Result<()> {
+        let ui_hashes: HashSet<&String> = ui_index
+            .values()
+            .filter_map(|indexed_app| indexed_app.ui_hash.as_ref())
+            .collect();
+        self.ui_archives
+            .write()
+            .map_err(|err| crate::Error::LockError(format!("{err:?}")))?
+            .retain(|ui_hash, _| ui_hashes.contains(ui_hash));
+        self.decompressed_ui_files
+            .lock()
+            .map_err(|err| crate::Error::LockError(format!("{err:?}")))?
+            .retain(|ui_hash| ui_hashes.contains(ui_hash));
+        Ok(())
+    }
+
+    /// The app id with its original casing and the hash of its UI, looked up ignoring the casing of the app id,
+    /// since webviews lowercase the host of the `happ` URLs
+    fn lookup_ui(&self, app_id: &str) -> crate::Result<(InstalledAppId, String)> {
+        let ui_index = self
+            .ui_index
+            .read()
+            .map_err(|err| crate::Error::LockError(format!("{err:?}")))?;
 
-        let Some(installed_app_info) = installed_apps.get(app_id) else {
-            return Err(crate::Error::AppDoesNotExist(app_id.clone()));
+        let Some(indexed_app) = ui_index.get(&app_id.to_lowercase()) else {
+            return Err(crate::Error::AppDoesNotExist(app_id.to_string()));
         };
-        let Some(installed_web_app_info) = &installed_app_info.web_app_info else {
-            return Err(crate::Error::AppDoesNotHaveUIError(app_id.clone()));
+        let Some(ui_hash) = &indexed_app.ui_hash else {
+            return Err(crate::Error::AppDoesNotHaveUIError(app_id.to_string()));
         };
 
-        let path = self
-            .ui_store()
-            .get_path_for_ui_with_hash(&installed_web_app_info.ui_hash);
+        Ok((indexed_app.app_id.clone(), ui_hash.clone()))
+    }
+
+    /// The UI installed for the given app, looked up ignoring the casing of the app id
+    pub fn get_ui(&self, app_id: &str) -> crate::Result<InstalledUi> {
+        let (app_id, ui_hash) = self.lookup_ui(app_id)?;
+
+        Ok(InstalledUi {
+            app_id,
+            content: self.ui_content(&ui_hash)?,
+            ui_hash,
+        })
+    }
+
+    /// The hash of the UI installed for the given app, which changes whenever any of its assets change
+    pub fn get_ui_hash(&self, app_id: &InstalledAppId) -> crate::Result<String> {
+        let (_app_id, ui_hash) = self.lookup_ui(app_id)?;
+        Ok(ui_hash)
+    }
+
+    /// Loads the archive of the UI with the given hash the first time it's needed,
+    /// falling back to the directory where it was extracted if it was installed before UIs were stored as archives
+    fn ui_content(&self, ui_hash: &String) -> crate::Result<UiContent> {
+        let ui_archives = self
+            .ui_archives
+            .read()
+            .map_err(|err| crate::Error::LockError(format!("{err:?}")))?;
+        if let Some(ui_archive) = ui_archives.get(ui_hash) {
+            return Ok(UiContent::Archive(ui_archive.clone()));
+        }
+        drop(ui_archives);
+
+        let ui_store = self.ui_store();
+        let archive_path = ui_store.get_archive_path_for_ui_with_hash(ui_hash);
+        if !archive_path.exists() {
+            return Ok(UiContent::Directory(
+                ui_store.get_path_for_ui_with_hash(ui_hash),
+            ));
+        }
+
+        let ui_archive = Arc::new(UiArchive::load(
+            &archive_path,
+            ui_hash,
+            self.decompressed_ui_files.clone(),
+        )?);
+        self.ui_archives
+            .write()
+            .map_err(|err| crate::Error::LockError(format!("{err:?}")))?
+            .insert(ui_hash.clone(), ui_archive.clone());
 
-        Ok(path)
+        Ok(UiContent::Archive(ui_archive))
     }
 
     pub fn store_happ_bundle(
@@ -113,6 +335,7 @@ impl BundleStore {
                 },
             );
         })?;
+        self.index_app(app_id, None)?;
 
         Ok(())
     }
@@ -131,12 +354,10 @@ impl BundleStore {
 
         let happ_bundle = web_app_bundle.happ_bundle().await?;
         let happ_bundle_hash = self.happ_bundle_store().store_app_bundle(&happ_bundle)?;
-        let ui_hash = self
-            .ui_store()
-            .extract_and_store_ui(&web_app_bundle)
-            .await?;
+        let ui_hash = self.ui_store().store_ui(&web_app_bundle).await?;
+        let previous_ui_hash = self.installed_ui_hash(&app_id)?;
 
-        self.installed_apps_store.update(move |installed_apps| {
+        self.installed_apps_store.update(|installed_apps| {
             installed_apps.insert(
                 app_id.clone(),
                 InstalledAppInfo {
@@ -147,7 +368,15 @@ impl BundleStore {
                     }),
                 },
             );
-        })
+        })?;
+        self.index_app(app_id, Some(ui_hash.clone()))?;
+
+        // The UI that was replaced by an update
+        if let Some(previous_ui_hash) = previous_ui_hash.filter(|hash| hash.ne(&ui_hash)) {
+            self.delete_ui_if_unused(&previous_ui_hash)?;
+        }
+
+        Ok(())
     }
 }
 
@@ -165,50 +394,67 @@ pub struct InstalledAppInfo {
 
 pub type InstalledAppsInfo = HashMap<String, InstalledAppInfo>;
 
+pub type InstalledAppsStore = JsonStore<InstalledAppsInfo>;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct AgentKeyInfo {
+    /// Human readable label for the key, e.g. the name of the profile that uses it
+    pub label: Option<String>,
+    /// Index of the path from which the key was derived from the device seed,
+    /// `None` if the key was not generated by `HolochainRuntime::generate_agent_key()`
+    pub derivation_index: Option<u32>,
+}
+
+/// Agent keys info by their base64 encoded public key
+pub type AgentKeysInfo = HashMap<String, AgentKeyInfo>;
+
+pub type AgentKeysStore = JsonStore<AgentKeysInfo>;
+
+/// In-memory state persisted as a JSON file
 #[derive(Clone)]
-pub struct InstalledAppsStore {
+pub struct JsonStore<T> {
     json_config_path: PathBuf,
-    installed_apps: Arc<RwLock<InstalledAppsInfo>>,
+    state: Arc<RwLock<T>>,
 }
 
-impl InstalledAppsStore {
+impl<T: Serialize + DeserializeOwned + Default + Clone> JsonStore<T> {
     fn new(json_config_path: PathBuf) -> crate::Result<Self> {
-        let apps = if json_config_path.exists() {
+        let state = if json_config_path.exists() {
             let s = std::fs::read_to_string(json_config_path.clone())?;
 
-            let apps: HashMap<String, InstalledAppInfo> = serde_json::from_str(s.as_str())?;
-            apps
+            let state: T = serde_json::from_str(s.as_str())?;
+            state
         } else {
             let mut file = std::fs::File::create(json_config_path.clone())?;
 
-            let apps: HashMap<String, InstalledAppInfo> = HashMap::new();
+            let state = T::default();
 
-            let data = serde_json::to_string(&apps)?;
+            let data = serde_json::to_string(&state)?;
 
             file.write(&data.as_bytes())?;
-            apps
+            state
         };
 
         Ok(Self {
             json_config_path,
-            installed_apps: Arc::new(RwLock::new(apps)),
+            state: Arc::new(RwLock::new(state)),
         })
     }
 
-    pub fn get(&self) -> crate::Result<InstalledAppsInfo> {
-        let apps = self
-            .installed_apps
+    pub fn get(&self) -> crate::Result<T> {
+        let state = self
+            .state
             .read()
             .map_err(|err| crate::Error::LockError(format!("{err:?}")))?;
-        Ok(apps.clone())
+        Ok(state.clone())
     }
 
     pub fn update<F>(&self, update_fn: F) -> crate::Result<()>
     where
-        F: Fn(&mut InstalledAppsInfo) -> (),
+        F: Fn(&mut T) -> (),
     {
         let mut write_lock = self
-            .installed_apps
+            .state
             // .write_arc()
             .write()
             .map_err(|err| crate::Error::LockError(format!("{err:?}")))?;
@@ -240,36 +486,274 @@ pub struct UiStore {
 }
 
 impl UiStore {
-    pub async fn extract_and_store_ui(
-        &self,
-        web_app: &WebAppBundle,
-    ) -> Result<String, FileSystemError> {
+    /// Stores the UI of the given web-app as a zip archive named after its hash,
+    /// from which its files are served without extracting them
+    /// Installing the same UI again reuses the archive already stored
+    pub async fn store_ui(&self, web_app: &WebAppBundle) -> Result<String, FileSystemError> {
         let ui_bytes = web_app.web_ui_zip_bytes().await?;
 
         let hash = sha256::digest(ui_bytes.to_vec());
 
-        let ui_folder_path = self.path.join(&hash);
+        let ui_archive_path = self.get_archive_path_for_ui_with_hash(&hash);
+        if ui_archive_path.exists() {
+            return Ok(hash);
+        }
+
+        let ui_bytes = ui_bytes.into_owned().into_inner();
+        // Fail the install if the UI is not a valid zip, instead of when serving it
+        ZipArchive::new(Cursor::new(ui_bytes.as_slice()))?;
+
+        // Write it to a temporary file that's only moved into place when complete,
+        // so that concurrent installs never see or write to a partial archive
+        let mut temp_file = tempfile::NamedTempFile::new_in(&self.path)?;
+        temp_file.write_all(ui_bytes.as_slice())?;
+        temp_file
+            .persist(&ui_archive_path)
+            .map_err(|err| err.error)?;
+
+        Ok(hash)
+    }
+
+    fn get_archive_path_for_ui_with_hash(&self, ui_hash: &String) -> PathBuf {
+        self.path.join(format!("{ui_hash}.zip"))
+    }
+
+    fn get_path_for_ui_with_hash(&self, ui_hash: &String) -> PathBuf {
+        self.path.join(ui_hash)
+    }
+}
+
+/// Largest total size of the decompressed UI files kept in memory to serve range requests
+const DECOMPRESSED_UI_FILES_CACHE_SIZE: usize = 64 * 1024 * 1024;
+
+/// The compressed files of the UI archives that were decompressed to serve range requests,
+/// so that seeking in compressed media doesn't decompress it from its start on every request
+/// Holds the least recently used files first, and evicts them once their total size exceeds the cache size
+#[derive(Default)]
+struct DecompressedUiFiles {
+    /// The UI hash, the name and the contents of each file
+    files: VecDeque<(String, String, Arc<[u8]>)>,
+    size: usize,
+}
 
-        if ui_folder_path.exists() {
-            fs::remove_dir_all(&ui_folder_path)?;
+impl DecompressedUiFiles {
+    fn get(&mut self, ui_hash: &str, name: &str) -> Option<Arc<[u8]>> {
+        let position = self
+            .files
+            .iter()
+            .position(|(hash, file_name, _)| hash == ui_hash && file_name == name)?;
+        let file = self.files.remove(position)?;
+        let bytes = file.2.clone();
+        self.files.push_back(file);
+        Some(bytes)
+    }
+
+    fn insert(&mut self, ui_hash: String, name: String, bytes: Arc<[u8]>) {
+        if self.get(&ui_hash, &name).is_some() || bytes.len() > DECOMPRESSED_UI_FILES_CACHE_SIZE {
+            return;
+        }
+        self.size += bytes.len();
+        self.files.push_back((ui_hash, name, bytes));
+        while self.size > DECOMPRESSED_UI_FILES_CACHE_SIZE {
+            let Some((_, _, evicted)) = self.files.pop_front() else {
+                break;
+            };
+            self.size -= evicted.len();
         }
+    }
+
+    fn retain(&mut self, keep_ui_hash: impl Fn(&String) -> bool) {
+        self.files.retain(|(ui_hash, _, _)| keep_ui_hash(ui_hash));
+        self.size = self.files.iter().map(|(_, _, bytes)| bytes.len()).sum();
+    }
+}
+
+/// A file inside a `UiArchive`
+#[derive(Debug, Clone, Copy)]
+struct ArchivedFile {
+    index: usize,
+    /// The uncompressed size of the file
+    size: u64,
+    compression: CompressionMethod,
+    /// The offset in the archive at which the contents of the file start
+    data_start: u64,
+    compressed_size: u64,
+}
+
+/// The zip archive of a UI, read from disk after checking once that it matches its hash
+/// Only the index of its files is kept in memory
+pub struct UiArchive {
+    path: PathBuf,
+    ui_hash: String,
+    /// The files in the archive by their path relative to the root of the UI, without directories
+    files: HashMap<String, ArchivedFile>,
+    decompressed_files: Arc<Mutex<DecompressedUiFiles>>,
+}
 
-        fs::create_dir_all(&ui_folder_path)?;
+impl UiArchive {
+    fn load(
+        path: &Path,
+        ui_hash: &String,
+        decompressed_files: Arc<Mutex<DecompressedUiFiles>>,
+    ) -> crate::Result<Self> {
+        // Hash the archive as it's read, instead of reading it all in memory
+        let mut hashing_writer = HashingWriter::new(io::sink());
+        io::copy(&mut fs::File::open(path)?, &mut hashing_writer)?;
+        if hashing_writer.finalize().ne(ui_hash) {
+            return Err(crate::Error::FilesystemError(format!(
+                "The UI archive {path:?} does not match its hash"
+            )));
+        }
 
-        let ui_zip_path = self.path.join("ui.zip");
+        let mut archive = ZipArchive::new(fs::File::open(path)?).map_err(FileSystemError::from)?;
 
-        fs::write(ui_zip_path.clone(), ui_bytes.into_owned().into_inner())?;
+        let mut files = HashMap::new();
+        for index in 0..archive.len() {
+            let file = archive.by_index_raw(index).map_err(FileSystemError::from)?;
+            if file.is_dir() {
+                continue;
+            }
+            // Skip the entries that would be extracted outside of the UI
+            let Some(path) = file.enclosed_name() else {
+                continue;
+            };
+            let name: Vec<&str> = path
+                .components()
+                .filter_map(|component| match component {
+                    Component::Normal(segment) => segment.to_str(),
+                    _ => None,
+                })
+                .collect();
+            files.insert(
+                name.join("/"),
+                ArchivedFile {
+                    index,
+                    size: file.size(),
+                    compression: file.compression(),
+                    data_start: file.data_start(),
+                    compressed_size: file.compressed_size(),
+                },
+            );
+        }
 
-        let file = std::fs::File::open(ui_zip_path.clone())?;
-        unzip_file(file, ui_folder_path)?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            ui_hash: ui_hash.clone(),
+            files,
+            decompressed_files,
+        })
+    }
 
-        fs::remove_file(ui_zip_path)?;
+    /// The paths of all the files in the UI, relative to its root
+    pub fn file_names(&self) -> impl Iterator<Item = &String> {
+        self.files.keys()
+    }
 
-        Ok(hash)
+    pub fn contains(&self, name: &str) -> bool {
+        self.files.contains_key(name)
     }
 
-    fn get_path_for_ui_with_hash(&self, ui_hash: &String) -> PathBuf {
-        self.path.join(ui_hash)
+    /// The uncompressed size of the given file, `None` if the UI doesn't have it
+    pub fn file_size(&self, name: &str) -> Option<u64> {
+        self.files.get(name).map(|file| file.size)
+    }
+
+    /// Opens the given file to read its uncompressed contents from its start, `None` if the UI doesn't have it
+    pub fn open_file(&self, name: &str) -> Result<Option<Box<dyn Read + Send>>, FileSystemError> {
+        let Some(archived_file) = self.files.get(name) else {
+            return Ok(None);
+        };
+        let mut file = fs::File::open(&self.path)?;
+        file.seek(SeekFrom::Start(archived_file.data_start))?;
+
+        let reader: Box<dyn Read + Send> = match archived_file.compression {
+            CompressionMethod::Stored => Box::new(file.take(archived_file.size)),
+            CompressionMethod::Deflated => Box::new(DeflateDecoder::new(
+                file.take(archived_file.compressed_size),
+            )),
+            // Other compression methods are rare in UIs, so the zip crate decompresses them whole
+            _ => {
+                let mut archive = ZipArchive::new(file)?;
+                let mut zip_file = archive.by_index(archived_file.index)?;
+                let mut bytes = Vec::with_capacity(archived_file.size as usize);
+                zip_file.read_to_end(&mut bytes)?;
+                Box::new(Cursor::new(bytes))
+            }
+        };
+        Ok(Some(reader))
+    }
+
+    /// Decompresses the given file, `None` if the UI doesn't have it
+    pub fn read(&self, name: &str) -> Result<Option<Vec<u8>>, FileSystemError> {
+        let Some(mut reader) = self.open_file(name)? else {
+            return Ok(None);
+        };
+        let mut bytes = Vec::with_capacity(self.file_size(name).unwrap_or_default() as usize);
+        reader.read_to_end(&mut bytes)?;
+        Ok(Some(bytes))
+    }
+
+    /// Reads up to `length` bytes of the given file from `start`, `None` if the UI doesn't have it
+    ///
+    /// Stored files are read from their range in the archive, and compressed ones are decompressed once
+    /// and kept in memory while they fit in the cache shared by all UIs
+    pub fn read_range(
+        &self,
+        name: &str,
+        start: u64,
+        length: u64,
+    ) -> Result<Option<Vec<u8>>, FileSystemError> {
+        let Some(archived_file) = self.files.get(name) else {
+            return Ok(None);
+        };
+        let start = start.min(archived_file.size);
+        let length = length.min(archived_file.size - start);
+
+        if archived_file.compression == CompressionMethod::Stored {
+            let mut file = fs::File::open(&self.path)?;
+            file.seek(SeekFrom::Start(archived_file.data_start + start))?;
+            let mut bytes = Vec::with_capacity(length as usize);
+            file.take(length).read_to_end(&mut bytes)?;
+            return Ok(Some(bytes));
+        }
+
+        if archived_file.size as usize <= DECOMPRESSED_UI_FILES_CACHE_SIZE {
+            let cached_bytes = self.decompressed_files().get(&self.ui_hash, name);
+            let bytes = match cached_bytes {
+                Some(bytes) => bytes,
+                None => {
+                    let Some(bytes) = self.read(name)? else {
+                        return Ok(None);
+                    };
+                    let bytes: Arc<[u8]> = Arc::from(bytes);
+                    self.decompressed_files().insert(
+                        self.ui_hash.clone(),
+                        name.to_string(),
+                        bytes.clone(),
+                    );
+                    bytes
+                }
+            };
+            let end = ((start + length) as usize).min(bytes.len());
+            let start = (start as usize).min(end);
+            return Ok(Some(bytes[start..end].to_vec()));
+        }
+
+        // Too large to be cached, so decompress it up to the end of the range without keeping the rest
+        let Some(mut reader) = self.open_file(name)? else {
+            return Ok(None);
+        };
+        io::copy(&mut (&mut reader).take(start), &mut io::sink())?;
+        let mut bytes = Vec::with_capacity(length as usize);
+        reader.take(length).read_to_end(&mut bytes)?;
+        Ok(Some(bytes))
+    }
+
+    fn decompressed_files(&self) -> MutexGuard<'_, DecompressedUiFiles> {
+        // The cache is never left inconsistent, so it can still be used if another thread panicked while holding it
+        self.decompressed_files
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
     }
 }
 
@@ -343,3 +827,128 @@ pub fn unzip_file(reader: std::fs::File, outpath: PathBuf) -> Result<(), FileSys
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ui_zip() -> Vec<u8> {
+        let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        let options = zip::write::FileOptions::default();
+        zip.add_directory("assets/", options).unwrap();
+        zip.start_file("index.html", options).unwrap();
+        zip.write_all(b"<html></html>").unwrap();
+        zip.start_file("./assets/main.js", options).unwrap();
+        zip.write_all(b"main()").unwrap();
+        zip.start_file(
+            "assets/video.mp4",
+            options.compression_method(CompressionMethod::Stored),
+        )
+        .unwrap();
+        zip.write_all(b"0123456789").unwrap();
+        zip.start_file("../secret", options).unwrap();
+        zip.write_all(b"secret").unwrap();
+        zip.finish().unwrap().into_inner()
+    }
+
+    fn load_ui_archive(dir: &Path, bytes: &[u8], ui_hash: &String) -> crate::Result<UiArchive> {
+        let path = dir.join(format!("{}.zip", sha256::digest(bytes)));
+        fs::write(&path, bytes).unwrap();
+        UiArchive::load(&path, ui_hash, Default::default())
+    }
+
+    #[test]
+    fn serves_files_from_the_ui_archive() {
+        let dir = tempfile::tempdir().unwrap();
+        let bytes = ui_zip();
+        let ui_archive = load_ui_archive(dir.path(), &bytes, &sha256::digest(&bytes)).unwrap();
+
+        let mut file_names: Vec<&String> = ui_archive.file_names().collect();
+        file_names.sort();
+        assert_eq!(
+            file_names,
+            vec!["assets/main.js", "assets/video.mp4", "index.html"]
+        );
+        assert_eq!(ui_archive.file_size("assets/main.js"), Some(6));
+        assert_eq!(
+            ui_archive.read("index.html").unwrap(),
+            Some(b"<html></html>".to_vec())
+        );
+        assert!(ui_archive.read("secret").unwrap().is_none());
+    }
+
+    #[test]
+    fn reads_ranges_of_stored_and_compressed_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let bytes = ui_zip();
+        let ui_archive = load_ui_archive(dir.path(), &bytes, &sha256::digest(&bytes)).unwrap();
+
+        assert_eq!(
+            ui_archive.read_range("assets/video.mp4", 2, 3).unwrap(),
+            Some(b"234".to_vec())
+        );
+        assert_eq!(
+            ui_archive.read_range("assets/video.mp4", 8, 10).unwrap(),
+            Some(b"89".to_vec())
+        );
+        for _ in 0..2 {
+            assert_eq!(
+                ui_archive.read_range("index.html", 1, 4).unwrap(),
+                Some(b"html".to_vec())
+            );
+        }
+        assert!(ui_archive.read_range("secret", 0, 1).unwrap().is_none());
+    }
+
+    #[test]
+    fn deletes_ui_archives_once_no_app_uses_them() {
+        let dir = tempfile::tempdir().unwrap();
+        let bundle_store = BundleStore::new(dir.path().to_path_buf()).unwrap();
+        let bytes = ui_zip();
+        let ui_hash = sha256::digest(&bytes);
+        let archive_path = bundle_store
+            .ui_store()
+            .get_archive_path_for_ui_with_hash(&ui_hash);
+        fs::write(&archive_path, &bytes).unwrap();
+
+        for app_id in ["forum", "forum-2"] {
+            let web_app_info = InstalledWebAppInfo {
+                ui_hash: ui_hash.clone(),
+                web_happ_bundle_hash: String::from("web-happ-hash"),
+            };
+            bundle_store
+                .installed_apps_store
+                .update(|installed_apps| {
+                    installed_apps.insert(
+                        app_id.to_string(),
+                        InstalledAppInfo {
+                            happ_bundle_hash: String::from("happ-hash"),
+                            web_app_info: Some(web_app_info.clone()),
+                        },
+                    );
+                })
+                .unwrap();
+            bundle_store
+                .index_app(app_id.to_string(), Some(ui_hash.clone()))
+                .unwrap();
+        }
+        assert!(bundle_store.get_ui("forum").is_ok());
+
+        bundle_store.remove_app(&String::from("forum")).unwrap();
+        assert!(archive_path.exists());
+        assert!(bundle_store.get_ui("forum").is_err());
+        assert!(bundle_store.get_ui("forum-2").is_ok());
+
+        bundle_store.remove_app(&String::from("forum-2")).unwrap();
+        assert!(!archive_path.exists());
+    }
+
+    #[test]
+    fn rejects_ui_archives_that_dont_match_their_hash() {
+        let dir = tempfile::tempdir().unwrap();
+        let bytes = ui_zip();
+
+        assert!(load_ui_archive(dir.path(), &bytes, &sha256::digest(&bytes)).is_ok());
+        assert!(load_ui_archive(dir.path(), &bytes, &sha256::digest("other ui")).is_err());
+    }
+}
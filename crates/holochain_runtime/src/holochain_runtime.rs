@@ -1,10 +1,11 @@
 use std::{
-    collections::HashMap,
+    collections::{BTreeSet, HashMap},
     net::{Ipv4Addr, SocketAddr},
+    path::PathBuf,
     sync::Arc,
 };
 
-use async_std::sync::Mutex;
+use async_std::sync::{Mutex, RwLock};
 use holochain::{
     conductor::ConductorHandle,
     prelude::{DisabledAppReason, NetworkSeed, ZomeCallParams},
@@ -22,15 +23,22 @@ use holochain_types::{
 use lair_keystore::dependencies::futures::future::join_all;
 use lair_keystore_api::types::SharedLockedArray;
 
+#[cfg(feature = "local-servers")]
+use crate::LocalServers;
 use crate::{
+    backup::{check_backup_path, restore_backup_archive, write_backup_archive, BackupManifest},
     filesystem::{AppBundleStore, BundleStore, FileSystem},
     happs::{
         install::install_app,
         update::{update_app, UpdateHappError},
     },
     lair_signer::LairAgentSignerWithProvenance,
-    launch::launch_holochain_runtime,
-    sign_zome_call_with_client, HolochainRuntimeConfig,
+    launch::{device_seed, keystore, launch_holochain_runtime, passphrase, ConductorLauncher},
+    mdns::{MdnsDiscovery, MdnsDiscoverySettings, MdnsParticipants},
+    shutdown::ShutdownState,
+    sign_zome_call_with_client,
+    supervisor::SupervisorListener,
+    HolochainRuntimeConfig, NetworkProfile, SupervisorEvent,
 };
 
 const NETWORK_SHUTDOWN_DISABLED_APP_REASON: &'static str = "holochain_runtime/network_shutdown";
@@ -48,7 +56,17 @@ pub struct HolochainRuntime {
     pub filesystem: FileSystem,
     pub apps_websockets_auths: Arc<Mutex<Vec<AppWebsocketAuth>>>,
     pub admin_port: u16,
-    pub conductor_handle: ConductorHandle,
+    /// Behind a lock since the conductor is replaced when it's relaunched with `Self::set_network_profile()`
+    pub(crate) conductor_handle: Arc<RwLock<ConductorHandle>>,
+    pub(crate) conductor_launcher: Arc<ConductorLauncher>,
+    /// The mDNS discovery service, which only runs while it's enabled
+    pub mdns_discovery: MdnsDiscovery,
+    /// The in-process bootstrap and signal servers, if `HolochainRuntimeConfig::spawn_local_servers()` was used
+    #[cfg(feature = "local-servers")]
+    pub local_servers: Option<LocalServers>,
+    pub(crate) shutdown_state: Arc<ShutdownState>,
+    /// Set by `Self::spawn_supervisor()`, also notified when the conductor is relaunched on purpose
+    pub(crate) supervisor_listener: Arc<std::sync::RwLock<Option<SupervisorListener>>>,
 }
 
 impl HolochainRuntime {
@@ -60,35 +78,41 @@ impl HolochainRuntime {
 
         let admin_ws = runtime.admin_websocket().await?;
 
-        let apps = admin_ws
+        let disabled_apps = admin_ws
             .list_apps(Some(holochain_client::AppStatusFilter::Disabled))
             .await?;
+        let disabled_app_ids: BTreeSet<InstalledAppId> = disabled_apps
+            .iter()
+            .map(|app| app.installed_app_id.clone())
+            .collect();
 
-        if !apps.is_empty() {
+        // Forget the apps that were enabled or uninstalled since the runtime disabled them
+        runtime
+            .filesystem
+            .apps_disabled_by_runtime_store
+            .update(|app_ids| app_ids.retain(|app_id| disabled_app_ids.contains(app_id)))?;
+        let mut apps_disabled_by_runtime =
+            runtime.filesystem.apps_disabled_by_runtime_store.get()?;
+
+        // Apps disabled in shutdown by previous versions are only recognizable by their disabled reason
+        apps_disabled_by_runtime.extend(
+            disabled_apps
+                .into_iter()
+                .filter(|app| match &app.status {
+                    AppInfoStatus::Disabled {
+                        reason: DisabledAppReason::Error(e),
+                    } => e.eq(NETWORK_SHUTDOWN_DISABLED_APP_REASON),
+                    _ => false,
+                })
+                .map(|app| app.installed_app_id),
+        );
+
+        if !apps_disabled_by_runtime.is_empty() {
             log::info!("Re-enabling all apps disabled in shutdown.");
 
-            join_all(apps.into_iter().map(async |app| {
-                let AppInfoStatus::Disabled { reason } = app.status else {
-                    return ();
-                };
-                let DisabledAppReason::Error(e) = reason else {
-                    return ();
-                };
-
-                if e.ne(&NETWORK_SHUTDOWN_DISABLED_APP_REASON.to_string()) {
-                    return ();
-                }
-
-                if let Err(err) = runtime
-                    .conductor_handle
-                    .clone()
-                    .enable_app(app.installed_app_id)
-                    .await
-                {
-                    log::error!("Error re-enabling the app: {err:?}.");
-                }
-            }))
-            .await;
+            runtime
+                .enable_apps(apps_disabled_by_runtime.into_iter().collect())
+                .await;
 
             log::info!("Re-enabled all apps disabled in shutdown.");
         }
@@ -96,6 +120,199 @@ impl HolochainRuntime {
         Ok(runtime)
     }
 
+    /// Checks whether the given passphrase unlocks the keystore stored in `holochain_dir`
+    /// This can be called before launching the runtime, to prompt the user for the passphrase until it's the right one
+    ///
+    /// * `holochain_dir` - the directory where the holochain files are stored
+    /// * `passphrase` - the passphrase to check
+    pub async fn verify_passphrase(
+        holochain_dir: PathBuf,
+        passphrase: SharedLockedArray,
+    ) -> crate::Result<bool> {
+        let filesystem = FileSystem::new(holochain_dir).await?;
+
+        // Keystore created before its passphrase was wrapped, which still uses the given passphrase
+        if !filesystem.keystore_passphrase_path().exists()
+            && filesystem.keystore_config_path().exists()
+        {
+            return keystore::keystore_accepts_passphrase(
+                &filesystem.keystore_config_path(),
+                passphrase,
+            )
+            .await
+            .map_err(|err| crate::Error::LairError(err));
+        }
+
+        tokio::task::spawn_blocking(move || passphrase::verify_passphrase(&filesystem, passphrase))
+            .await
+            .map_err(|err| crate::Error::PassphraseError(format!("{err:?}")))?
+    }
+
+    /// Changes the passphrase that unlocks the keystore
+    /// Fails with `Error::WrongPassphrase` if `old_passphrase` is not the current passphrase
+    ///
+    /// * `old_passphrase` - the current passphrase
+    /// * `new_passphrase` - the passphrase to unlock the keystore with from now on
+    pub async fn change_passphrase(
+        &self,
+        old_passphrase: SharedLockedArray,
+        new_passphrase: SharedLockedArray,
+    ) -> crate::Result<()> {
+        let filesystem = self.filesystem.clone();
+
+        tokio::task::spawn_blocking(move || {
+            passphrase::change_passphrase(&filesystem, old_passphrase, new_passphrase)
+        })
+        .await
+        .map_err(|err| crate::Error::PassphraseError(format!("{err:?}")))?
+    }
+
+    /// Exports the device seed, from which the agent keys are derived, encrypted with the given passphrase
+    /// The backup can be restored on a fresh install with `HolochainRuntimeConfig::restore_device_seed_backup()`
+    ///
+    /// * `passphrase` - the passphrase or recovery phrase to encrypt the backup with
+    pub async fn export_device_seed_backup(
+        &self,
+        passphrase: SharedLockedArray,
+    ) -> crate::Result<Vec<u8>> {
+        device_seed::export_device_seed_backup(
+            &self.conductor_launcher.keystore().await,
+            passphrase,
+        )
+        .await
+    }
+
+    /// The current mDNS discovery settings
+    pub fn mdns_discovery_settings(&self) -> crate::Result<MdnsDiscoverySettings> {
+        self.mdns_discovery.settings()
+    }
+
+    /// Enables or disables the discovery of peers in the local network via mDNS
+    /// The setting is persisted, and overrides `HolochainRuntimeConfig::mdns_discovery` from now on
+    ///
+    /// * `enabled` - whether the discovery should run
+    pub async fn set_mdns_discovery_enabled(&self, enabled: bool) -> crate::Result<()> {
+        let mut settings = self.mdns_discovery.settings()?;
+        settings.enabled = enabled;
+        self.set_mdns_discovery_settings(settings).await
+    }
+
+    /// Sets which apps and DNAs broadcast their agents and look for peers in the local network via mDNS
+    /// The spaces of the apps that don't participate are not revealed in the local network
+    ///
+    /// * `participants` - the apps and DNAs that participate in the discovery
+    pub async fn set_mdns_discovery_participants(
+        &self,
+        participants: MdnsParticipants,
+    ) -> crate::Result<()> {
+        let mut settings = self.mdns_discovery.settings()?;
+        settings.participants = participants;
+        self.set_mdns_discovery_settings(settings).await
+    }
+
+    async fn set_mdns_discovery_settings(
+        &self,
+        settings: MdnsDiscoverySettings,
+    ) -> crate::Result<()> {
+        self.filesystem
+            .mdns_discovery_settings_store
+            .update(|stored_settings| {
+                *stored_settings = Some(settings.clone());
+            })?;
+        self.mdns_discovery.apply_settings(settings).await
+    }
+
+    /// The handle to the running conductor
+    /// Note that the conductor is replaced when it's relaunched with `Self::set_network_profile()`,
+    /// so don't hold on to the returned handle
+    pub async fn conductor_handle(&self) -> ConductorHandle {
+        self.conductor_handle.read().await.clone()
+    }
+
+    /// The network profile the conductor is running with
+    pub async fn network_profile(&self) -> NetworkProfile {
+        self.conductor_launcher.network_profile.lock().await.clone()
+    }
+
+    /// Switches to the given network profile, and persists it to be used in the next launches
+    ///
+    /// Holochain can't change the network config of a running conductor, so this relaunches the conductor in-process:
+    /// all running apps leave their networks, the conductor is shut down and launched again with the new network config,
+    /// and the apps are enabled again
+    /// The app websockets are attached again on the same ports with new tokens, which are sent to the `on_event` callback
+    /// of `Self::spawn_supervisor()` as a `SupervisorEvent::ConductorRestarted`
+    ///
+    /// If the conductor fails to launch with the new profile, it's launched again with the current one,
+    /// which stays persisted, and the error is returned
+    ///
+    /// * `network_profile` - the network profile to switch to
+    pub async fn set_network_profile(&self, network_profile: NetworkProfile) -> crate::Result<()> {
+        let mut current_network_profile = self.conductor_launcher.network_profile.lock().await;
+        self.ensure_running().await?;
+
+        if current_network_profile.eq(&network_profile) {
+            return self.persist_network_profile(network_profile);
+        }
+
+        log::info!("Relaunching the conductor with network profile {network_profile:?}.");
+
+        self.mdns_discovery.shutdown()?;
+        let disabled_apps = self.disable_running_apps().await?;
+
+        if let Err(err) = self.shutdown_conductor().await {
+            self.enable_apps(disabled_apps).await;
+            self.mdns_discovery
+                .set_network_profile(current_network_profile.clone())
+                .await?;
+            return Err(err);
+        }
+
+        let app_websockets_auths = match self.start_conductor(&network_profile).await {
+            Ok(app_websockets_auths) => app_websockets_auths,
+            Err(err) => {
+                log::error!(
+                    "Failed to relaunch the conductor with network profile {network_profile:?}, relaunching it with {:?}: {err:?}.",
+                    *current_network_profile
+                );
+                // The new conductor may have been built before failing, holding the admin port and the databases
+                if let Err(err) = self.conductor_handle().await.shutdown().await {
+                    log::warn!("Error shutting down the failed conductor: {err:?}.");
+                }
+                let app_websockets_auths = self.start_conductor(&current_network_profile).await?;
+                self.notify_supervisor_listener(SupervisorEvent::ConductorRestarted(
+                    app_websockets_auths,
+                ));
+                return Err(err);
+            }
+        };
+        *current_network_profile = network_profile.clone();
+        self.persist_network_profile(network_profile)?;
+
+        log::info!("Relaunched the conductor.");
+
+        self.notify_supervisor_listener(SupervisorEvent::ConductorRestarted(app_websockets_auths));
+
+        Ok(())
+    }
+
+    fn persist_network_profile(&self, network_profile: NetworkProfile) -> crate::Result<()> {
+        self.filesystem
+            .network_profile_store
+            .update(|stored_network_profile| {
+                *stored_network_profile = Some(network_profile.clone());
+            })
+    }
+
+    /// Shuts down the running conductor, closing its databases
+    async fn shutdown_conductor(&self) -> crate::Result<()> {
+        self.conductor_handle()
+            .await
+            .shutdown()
+            .await
+            .map_err(|e| crate::Error::HolochainShutdownError(e.to_string()))?
+            .map_err(|e| crate::Error::HolochainShutdownError(e.to_string()))
+    }
+
     /// Builds an `AdminWebsocket` ready to use
     pub async fn admin_websocket(&self) -> crate::Result<AdminWebsocket> {
         let mut config = WebsocketConfig::CLIENT_DEFAULT;
@@ -116,6 +333,8 @@ impl HolochainRuntime {
         app_id: &InstalledAppId,
         allowed_origins: AllowedOrigins,
     ) -> crate::Result<AppWebsocketAuth> {
+        self.ensure_running().await?;
+
         let mut apps_websockets_auths = self.apps_websockets_auths.lock().await;
         let existing_auth = apps_websockets_auths
             .iter()
@@ -182,7 +401,11 @@ impl HolochainRuntime {
             config,
             app_websocket_auth.token,
             Arc::new(LairAgentSignerWithProvenance::new(Arc::new(
-                self.conductor_handle.keystore().lair_client().clone(),
+                self.conductor_launcher
+                    .keystore()
+                    .await
+                    .lair_client()
+                    .clone(),
             ))),
         )
         .await
@@ -380,9 +603,16 @@ impl HolochainRuntime {
         &self,
         zome_call_unsigned: ZomeCallParams,
     ) -> crate::Result<ZomeCallParamsSigned> {
+        self.ensure_running().await?;
+
         let signed_zome_call = sign_zome_call_with_client(
             zome_call_unsigned,
-            &self.conductor_handle.keystore().lair_client().clone(),
+            &self
+                .conductor_launcher
+                .keystore()
+                .await
+                .lair_client()
+                .clone(),
         )
         .await?;
         Ok(signed_zome_call)
@@ -402,12 +632,14 @@ impl HolochainRuntime {
         Ok(matching_app.is_some())
     }
 
-    /// Uninstall the app with the given `app_id` from the holochain conductor
+    /// Uninstall the app with the given `app_id` from the holochain conductor,
+    /// and forget its bundles so that the archive of its UI is released
     ///
     /// * `app_id` - the app id of the app to uninstall
     pub async fn uninstall_app(&self, app_id: InstalledAppId) -> crate::Result<()> {
         let admin_ws = self.admin_websocket().await?;
-        admin_ws.uninstall_app(app_id, false).await?;
+        admin_ws.uninstall_app(app_id.clone(), false).await?;
+        self.filesystem.bundle_store.remove_app(&app_id)?;
 
         Ok(())
     }
@@ -432,25 +664,29 @@ impl HolochainRuntime {
         Ok(())
     }
 
-    /// Shutdown the running conductor
-    /// Note that this is *NOT* fully implemented by Holochain,
-    /// so kitsune tasks will continue to run.
-    pub async fn shutdown(&self) -> crate::Result<()> {
-        // Leave all networks using `disable_app()`, which will make the cells leave the network
-        // and notify the bootstrap server and the peers about it
-
+    /// Disables all the running apps, recording them so that `Self::launch()` enables them again
+    /// even if the process dies before `Self::enable_apps()` is called
+    /// Returns the ids of the apps that were disabled
+    pub(crate) async fn disable_running_apps(&self) -> crate::Result<Vec<InstalledAppId>> {
         let admin_ws = self.admin_websocket().await?;
 
         let apps = admin_ws
             .list_apps(Some(holochain_client::AppStatusFilter::Enabled))
             .await?;
+        let app_ids: Vec<InstalledAppId> =
+            apps.into_iter().map(|app| app.installed_app_id).collect();
+
+        // Record the apps before disabling them, so that a crash in between can't leave them disabled
+        self.filesystem
+            .apps_disabled_by_runtime_store
+            .update(|disabled_app_ids| disabled_app_ids.extend(app_ids.iter().cloned()))?;
 
-        join_all(apps.into_iter().map(async |app| {
-            if let Err(err) = self
-                .conductor_handle
+        let conductor_handle = self.conductor_handle().await;
+        join_all(app_ids.iter().map(async |app_id| {
+            if let Err(err) = conductor_handle
                 .clone()
                 .disable_app(
-                    app.installed_app_id,
+                    app_id.clone(),
                     holochain::prelude::DisabledAppReason::Error(
                         NETWORK_SHUTDOWN_DISABLED_APP_REASON.into(),
                     ),
@@ -462,13 +698,159 @@ impl HolochainRuntime {
         }))
         .await;
 
-        log::info!("Disabled all running apps to leave their networks.");
+        Ok(app_ids)
+    }
 
-        self.conductor_handle
-            .shutdown()
+    /// Enables the given apps, logging the errors instead of failing
+    /// The apps that fail to be enabled stay recorded as disabled by the runtime, to be retried in the next launch
+    pub(crate) async fn enable_apps(&self, app_ids: Vec<InstalledAppId>) {
+        let conductor_handle = self.conductor_handle().await;
+        let enabled_app_ids: Vec<InstalledAppId> =
+            join_all(app_ids.into_iter().map(async |app_id| {
+                match conductor_handle.clone().enable_app(app_id.clone()).await {
+                    Ok(_) => Some(app_id),
+                    Err(err) => {
+                        log::error!("Error re-enabling the app: {err:?}.");
+                        None
+                    }
+                }
+            }))
             .await
-            .map_err(|e| crate::Error::HolochainShutdownError(e.to_string()))?
-            .map_err(|e| crate::Error::HolochainShutdownError(e.to_string()))?;
-        Ok(())
+            .into_iter()
+            .flatten()
+            .collect();
+
+        if let Err(err) =
+            self.filesystem
+                .apps_disabled_by_runtime_store
+                .update(|disabled_app_ids| {
+                    disabled_app_ids.retain(|app_id| !enabled_app_ids.contains(app_id))
+                })
+        {
+            log::error!("Error forgetting the re-enabled apps: {err:?}.");
+        }
+    }
+
+    /// Creates a backup archive with the keystore, the conductor databases, and the installed hApps and UIs
+    /// The running apps are disabled and the conductor and the keystore are stopped while the backup is being created,
+    /// so that their databases are consistent, and they are all started again afterwards
+    /// The app websockets are attached again on the same ports with new tokens, which are sent to the `on_event` callback
+    /// of `Self::spawn_supervisor()` as a `SupervisorEvent::ConductorRestarted`
+    ///
+    /// Restore it with `Self::restore_backup()` before launching the runtime
+    /// Note that the keystore can only be unlocked with the passphrase it had when the backup was created
+    ///
+    /// * `backup_path` - the path of the archive to create, which must be outside of the holochain directory
+    pub async fn create_backup(&self, backup_path: PathBuf) -> crate::Result<()> {
+        check_backup_path(&self.filesystem.app_data_dir, &backup_path)?;
+
+        // Also prevents the supervisor, `Self::set_network_profile()` and `Self::shutdown()` from relaunching
+        // or stopping the conductor meanwhile
+        let network_profile = self.conductor_launcher.network_profile.lock().await;
+        self.ensure_running().await?;
+
+        // Only what was actually stopped is started again if a step fails
+        self.mdns_discovery.shutdown()?;
+        let disabled_apps = match self.disable_running_apps().await {
+            Ok(disabled_apps) => disabled_apps,
+            Err(err) => {
+                self.mdns_discovery
+                    .set_network_profile(network_profile.clone())
+                    .await?;
+                return Err(err);
+            }
+        };
+        log::info!("Disabled all running apps to create the backup.");
+
+        if let Err(err) = self.shutdown_conductor().await {
+            self.enable_apps(disabled_apps).await;
+            self.mdns_discovery
+                .set_network_profile(network_profile.clone())
+                .await?;
+            return Err(err);
+        }
+
+        let result = match self.write_backup_while_keystore_stopped(backup_path).await {
+            Ok(result) => result,
+            Err(err) => {
+                self.handle_backup_restart_failure(disabled_apps, &network_profile, &err)
+                    .await;
+                return Err(err);
+            }
+        };
+
+        match self.start_conductor(&network_profile).await {
+            Ok(app_websockets_auths) => {
+                log::info!("Relaunched the conductor after creating the backup.");
+                self.notify_supervisor_listener(SupervisorEvent::ConductorRestarted(
+                    app_websockets_auths,
+                ));
+                result
+            }
+            Err(err) => {
+                self.handle_backup_restart_failure(disabled_apps, &network_profile, &err)
+                    .await;
+                Err(result.err().unwrap_or(err))
+            }
+        }
+    }
+
+    /// Stops the keystore, writes the backup archive, and spawns the keystore again if it was stopped
+    /// Returns the result of writing the backup, or the error if the keystore couldn't be spawned again
+    async fn write_backup_while_keystore_stopped(
+        &self,
+        backup_path: PathBuf,
+    ) -> crate::Result<crate::Result<()>> {
+        let keystore = self.conductor_launcher.keystore().await;
+        if let Err(err) = keystore.lair_client().shutdown().await {
+            return Ok(Err(crate::Error::LairError(err)));
+        }
+
+        let holochain_dir = self.filesystem.app_data_dir.clone();
+        let result =
+            tokio::task::spawn_blocking(move || write_backup_archive(&holochain_dir, &backup_path))
+                .await
+                .map_err(|err| crate::Error::BackupError(format!("{err:?}")))
+                .and_then(|result| result);
+
+        self.conductor_launcher.respawn_keystore().await?;
+
+        Ok(result)
+    }
+
+    /// Reports the failure to start the conductor again after creating a backup to the supervisor listener,
+    /// and tries to enable the disabled apps and mDNS discovery again
+    /// The apps that can't be enabled stay recorded as disabled by the runtime, so that the next launch enables them
+    async fn handle_backup_restart_failure(
+        &self,
+        disabled_apps: Vec<InstalledAppId>,
+        network_profile: &NetworkProfile,
+        err: &crate::Error,
+    ) {
+        log::error!("Failed to relaunch the conductor after creating the backup: {err:?}.");
+        self.notify_supervisor_listener(SupervisorEvent::RestartFailed(err.to_string()));
+
+        self.enable_apps(disabled_apps).await;
+        if let Err(err) = self
+            .mdns_discovery
+            .set_network_profile(network_profile.clone())
+            .await
+        {
+            log::error!("Failed to restart mDNS discovery: {err:?}.");
+        }
+    }
+
+    /// Restores a backup archive created with `Self::create_backup()`, verifying the integrity of all its files
+    /// This must be called before launching the runtime, and `holochain_dir` must not contain any files yet
+    ///
+    /// * `backup_path` - the path of the backup archive
+    /// * `holochain_dir` - the directory to restore the backup into, to be used as `HolochainRuntimeConfig::holochain_dir`
+    pub async fn restore_backup(
+        backup_path: PathBuf,
+        holochain_dir: PathBuf,
+    ) -> crate::Result<BackupManifest> {
+        tokio::task::spawn_blocking(move || restore_backup_archive(&backup_path, &holochain_dir))
+            .await
+            .map_err(|err| crate::Error::BackupError(format!("{err:?}")))?
     }
 }
@@ -1,21 +1,96 @@
 use std::sync::Arc;
 
-use async_std::sync::Mutex;
+use async_std::sync::{Mutex, RwLock};
+use holochain::conductor::ConductorHandle;
+use holochain_conductor_api::conductor::NetworkConfig;
+use holochain_keystore::MetaLairClient;
 use keystore::spawn_lair_keystore_in_proc;
 // use holochain_keystore::lair_keystore::spawn_lair_keystore_in_proc;
 use lair_keystore::dependencies::hc_seed_bundle::SharedLockedArray;
 
 use holochain::conductor::Conductor;
 
-use crate::{filesystem::FileSystem, HolochainRuntime, HolochainRuntimeConfig};
+#[cfg(feature = "dpki")]
+use crate::DpkiRuntimeConfig;
+#[cfg(feature = "local-servers")]
+use crate::LocalServers;
+use crate::{
+    filesystem::FileSystem,
+    mdns::{MdnsDiscovery, MdnsDiscoverySettings, MdnsParticipants},
+    shutdown::ShutdownState,
+    HolochainRuntime, HolochainRuntimeConfig, NetworkProfile,
+};
 
 mod config;
-mod keystore;
-mod mdns;
-use mdns::spawn_mdns_bootstrap;
+pub(crate) mod device_seed;
+pub(crate) mod keystore;
+pub(crate) mod passphrase;
 
 pub const DEVICE_SEED_LAIR_KEYSTORE_TAG: &'static str = "DEVICE_SEED";
 
+/// Everything needed to build the conductor again, to relaunch it in-process with another network config
+pub(crate) struct ConductorLauncher {
+    filesystem: FileSystem,
+    admin_port: u16,
+    /// Behind a lock since the keystore is respawned if it stops responding
+    keystore: RwLock<MetaLairClient>,
+    keystore_passphrase: SharedLockedArray,
+    wan_network_config: NetworkConfig,
+    #[cfg(feature = "dpki")]
+    dpki: Option<DpkiRuntimeConfig>,
+    /// The network profile the conductor is currently running with
+    /// Also held while relaunching the conductor, so that only one relaunch happens at a time
+    pub(crate) network_profile: Mutex<NetworkProfile>,
+}
+
+impl ConductorLauncher {
+    /// Builds and starts a new conductor with the network config for the given profile
+    pub(crate) async fn build_conductor(
+        &self,
+        network_profile: &NetworkProfile,
+    ) -> crate::Result<ConductorHandle> {
+        let conductor_config = config::conductor_config(
+            &self.filesystem,
+            self.admin_port,
+            self.filesystem.keystore_dir().into(),
+            network_profile.network_config(&self.wan_network_config),
+            #[cfg(feature = "dpki")]
+            self.dpki.clone(),
+        );
+
+        log::debug!("Built conductor config: {:?}.", conductor_config);
+
+        let conductor_handle = Conductor::builder()
+            .config(conductor_config)
+            .passphrase(Some(self.keystore_passphrase.clone()))
+            .with_keystore(self.keystore().await)
+            .build()
+            .await?;
+
+        Ok(conductor_handle)
+    }
+
+    /// The client for the running keystore
+    pub(crate) async fn keystore(&self) -> MetaLairClient {
+        self.keystore.read().await.clone()
+    }
+
+    /// Spawns the keystore again with the same passphrase, replacing the one that was running
+    pub(crate) async fn respawn_keystore(&self) -> crate::Result<()> {
+        let mut keystore = self.keystore.write().await;
+
+        *keystore = spawn_lair_keystore_in_proc(
+            &self.filesystem.keystore_config_path(),
+            self.keystore_passphrase.clone(),
+        )
+        .map_err(|err| crate::Error::LairError(err))?;
+
+        log::info!("Keystore respawned successfully.");
+
+        Ok(())
+    }
+}
+
 /// Launch the holochain conductor in the background
 pub(crate) async fn launch_holochain_runtime(
     passphrase: SharedLockedArray,
@@ -28,28 +103,58 @@ pub(crate) async fn launch_holochain_runtime(
         portpicker::pick_unused_port().expect("No ports free")
     };
 
-    let conductor_config = config::conductor_config(
-        &filesystem,
-        admin_port,
-        filesystem.keystore_dir().into(),
-        config.network_config,
-    );
-
-    log::debug!("Built conductor config: {:?}.", conductor_config);
-
-    let keystore =
-        spawn_lair_keystore_in_proc(&filesystem.keystore_config_path(), passphrase.clone())
-            .map_err(|err| crate::Error::LairError(err))?;
+    let mut unwrapped_keystore = false;
+    let keystore_passphrase =
+        match passphrase::unlock_keystore_passphrase(&filesystem, passphrase.clone())? {
+            Some(keystore_passphrase) => keystore_passphrase,
+            None if filesystem.keystore_config_path().exists() => {
+                // Keystore created before its passphrase was wrapped: it keeps using the given passphrase
+                if !keystore::keystore_accepts_passphrase(
+                    &filesystem.keystore_config_path(),
+                    passphrase.clone(),
+                )
+                .await
+                .map_err(|err| crate::Error::LairError(err))?
+                {
+                    return Err(crate::Error::WrongPassphrase);
+                }
+                unwrapped_keystore = true;
+                passphrase.clone()
+            }
+            None => passphrase::new_keystore_passphrase(&filesystem, passphrase.clone())?,
+        };
+
+    let keystore = spawn_lair_keystore_in_proc(
+        &filesystem.keystore_config_path(),
+        keystore_passphrase.clone(),
+    )
+    .map_err(|err| crate::Error::LairError(err))?;
 
     log::info!("Keystore spawned successfully.");
 
+    // Only wrap the passphrase of an existing keystore once lair has accepted it
+    if unwrapped_keystore {
+        passphrase::wrap_keystore_passphrase(
+            &filesystem,
+            passphrase,
+            keystore_passphrase.clone(),
+        )?;
+    }
+
     let seed_already_exists = keystore
         .lair_client()
         .get_entry(DEVICE_SEED_LAIR_KEYSTORE_TAG.into())
         .await
         .is_ok();
 
-    if !seed_already_exists {
+    if let Some(device_seed_backup) = config.device_seed_backup {
+        device_seed::restore_device_seed_backup(
+            &keystore,
+            &device_seed_backup.backup,
+            device_seed_backup.passphrase,
+        )
+        .await?;
+    } else if !seed_already_exists {
         keystore
             .lair_client()
             .new_seed(
@@ -61,23 +166,62 @@ pub(crate) async fn launch_holochain_runtime(
             .map_err(|err| crate::Error::LairError(err))?;
     }
 
-    let conductor_handle = Conductor::builder()
-        .config(conductor_config)
-        .passphrase(Some(passphrase))
-        .with_keystore(keystore)
-        .build()
-        .await?;
+    #[cfg(feature = "local-servers")]
+    let mut network_config = config.network_config;
+    #[cfg(not(feature = "local-servers"))]
+    let network_config = config.network_config;
+
+    #[cfg(feature = "local-servers")]
+    let local_servers = match config.local_servers {
+        Some(listen_address) => {
+            let local_servers = LocalServers::spawn(listen_address, config.local_servers_mode).await?;
+            network_config.bootstrap_url = local_servers.bootstrap_url();
+            network_config.signal_url = local_servers.signal_url();
+            Some(local_servers)
+        }
+        None => None,
+    };
+
+    let network_profile = filesystem
+        .network_profile_store
+        .get()?
+        .unwrap_or(config.network_profile);
+
+    let conductor_launcher = ConductorLauncher {
+        filesystem: filesystem.clone(),
+        admin_port,
+        keystore: RwLock::new(keystore),
+        keystore_passphrase,
+        wan_network_config: network_config,
+        #[cfg(feature = "dpki")]
+        dpki: config.dpki,
+        network_profile: Mutex::new(network_profile.clone()),
+    };
+
+    let conductor_handle = conductor_launcher.build_conductor(&network_profile).await?;
 
     log::info!("Connected to the admin websocket");
 
-    if config.mdns_discovery {
-        spawn_mdns_bootstrap(admin_port).await?;
-    }
+    let mdns_discovery_settings = filesystem
+        .mdns_discovery_settings_store
+        .get()?
+        .unwrap_or(MdnsDiscoverySettings {
+            enabled: config.mdns_discovery,
+            participants: MdnsParticipants::AllApps,
+        });
+    let mdns_discovery =
+        MdnsDiscovery::new(admin_port, mdns_discovery_settings, network_profile).await?;
 
     Ok(HolochainRuntime {
         filesystem,
         apps_websockets_auths: Arc::new(Mutex::new(Vec::new())),
         admin_port,
-        conductor_handle,
+        conductor_handle: Arc::new(RwLock::new(conductor_handle)),
+        conductor_launcher: Arc::new(conductor_launcher),
+        mdns_discovery,
+        #[cfg(feature = "local-servers")]
+        local_servers,
+        shutdown_state: Arc::new(ShutdownState::new(config.shutdown_timeout)),
+        supervisor_listener: Arc::new(std::sync::RwLock::new(None)),
     })
 }
@@ -6,6 +6,8 @@ use holochain_conductor_api::conductor::DpkiConfig;
 use holochain_keystore::paths::KeystorePath;
 use holochain_types::websocket::AllowedOrigins;
 
+#[cfg(feature = "dpki")]
+use crate::{DeepkeyDna, DpkiRuntimeConfig};
 use crate::{filesystem::FileSystem, launch::DEVICE_SEED_LAIR_KEYSTORE_TAG, NetworkConfig};
 
 pub fn conductor_config(
@@ -13,6 +15,7 @@ pub fn conductor_config(
     admin_port: u16,
     lair_root: KeystorePath,
     mut network_config: NetworkConfig,
+    #[cfg(feature = "dpki")] dpki: Option<DpkiRuntimeConfig>,
 ) -> ConductorConfig {
     let mut config = ConductorConfig::default();
     config.data_root_path = Some(fs.conductor_dir().into());
@@ -22,6 +25,17 @@ pub fn conductor_config(
     config.device_seed_lair_tag = Some(DEVICE_SEED_LAIR_KEYSTORE_TAG.into());
     config.dpki = DpkiConfig::disabled();
 
+    #[cfg(feature = "dpki")]
+    if let Some(dpki) = dpki {
+        let dna_path = match dpki.deepkey_dna {
+            DeepkeyDna::Bundled => None,
+            DeepkeyDna::Path(path) => Some(path),
+        };
+        let mut dpki_config = DpkiConfig::production(dna_path);
+        dpki_config.network_seed = dpki.network_seed;
+        config.dpki = dpki_config;
+    }
+
     // LAN
     if let None = network_config.advanced {
         let advanced_config = serde_json::json!({
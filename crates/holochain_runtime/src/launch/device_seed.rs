@@ -0,0 +1,178 @@
+use std::sync::{Arc, Mutex};
+
+use holochain_keystore::MetaLairClient;
+use lair_keystore::dependencies::{hc_seed_bundle::SharedLockedArray, sodoken::LockedArray};
+use lair_keystore_api::prelude::{LairEntryInfo, SeedInfo};
+use serde::{Deserialize, Serialize};
+
+use super::{passphrase::PassphraseEncrypted, DEVICE_SEED_LAIR_KEYSTORE_TAG};
+
+/// Seed in lair used to move the device seed in and out of lair encrypted
+const DEVICE_SEED_TRANSPORT_LAIR_KEYSTORE_TAG: &'static str = "DEVICE_SEED_TRANSPORT";
+
+const DEVICE_SEED_BACKUP_VERSION: u32 = 1;
+
+/// An encrypted backup of the device seed, from which all the agent keys are derived
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct DeviceSeedBackup {
+    version: u32,
+    /// The public key of the device seed, to verify that it was restored correctly
+    device_seed_pub_key: Vec<u8>,
+    device_seed: PassphraseEncrypted,
+}
+
+fn lair_error(err: one_err::OneErr) -> crate::Error {
+    crate::Error::LairError(err)
+}
+
+fn device_seed_backup_error(reason: &str) -> crate::Error {
+    crate::Error::DeviceSeedBackupError(reason.to_string())
+}
+
+async fn get_seed_info(keystore: &MetaLairClient, tag: &str) -> crate::Result<Option<SeedInfo>> {
+    match keystore.lair_client().get_entry(tag.into()).await {
+        Ok(LairEntryInfo::Seed { seed_info, .. }) => Ok(Some(seed_info)),
+        Ok(_) => Err(device_seed_backup_error(&format!("Lair entry {tag} is not a seed"))),
+        Err(_) => Ok(None),
+    }
+}
+
+async fn get_or_create_transport_seed(keystore: &MetaLairClient) -> crate::Result<SeedInfo> {
+    if let Some(seed_info) =
+        get_seed_info(keystore, DEVICE_SEED_TRANSPORT_LAIR_KEYSTORE_TAG).await?
+    {
+        return Ok(seed_info);
+    }
+
+    keystore
+        .lair_client()
+        .new_seed(DEVICE_SEED_TRANSPORT_LAIR_KEYSTORE_TAG.into(), None, false)
+        .await
+        .map_err(lair_error)
+}
+
+/// Exports the device seed encrypted with the given passphrase
+pub(crate) async fn export_device_seed_backup(
+    keystore: &MetaLairClient,
+    passphrase: SharedLockedArray,
+) -> crate::Result<Vec<u8>> {
+    let device_seed_info = get_seed_info(keystore, DEVICE_SEED_LAIR_KEYSTORE_TAG)
+        .await?
+        .ok_or(device_seed_backup_error("There is no device seed to export"))?;
+    let transport_seed_info = get_or_create_transport_seed(keystore).await?;
+    let transport_pub_key = transport_seed_info.x25519_pub_key;
+
+    // Lair only exports seeds encrypted for a recipient, so box it to lair itself and open it right away
+    let (nonce, cipher) = keystore
+        .lair_client()
+        .export_seed_by_tag(
+            DEVICE_SEED_LAIR_KEYSTORE_TAG.into(),
+            transport_pub_key.clone(),
+            transport_pub_key.clone(),
+            None,
+        )
+        .await
+        .map_err(lair_error)?;
+    let seed = keystore
+        .lair_client()
+        .crypto_box_xsalsa_open_by_pub_key(
+            transport_pub_key.clone(),
+            transport_pub_key,
+            None,
+            nonce,
+            cipher,
+        )
+        .await
+        .map_err(lair_error)?;
+    let seed: SharedLockedArray = Arc::new(Mutex::new(LockedArray::from(seed.to_vec())));
+
+    let device_seed =
+        tokio::task::spawn_blocking(move || PassphraseEncrypted::encrypt(passphrase, seed))
+            .await
+            .map_err(|err| crate::Error::DeviceSeedBackupError(format!("{err:?}")))??;
+
+    let backup = DeviceSeedBackup {
+        version: DEVICE_SEED_BACKUP_VERSION,
+        device_seed_pub_key: device_seed_info.ed25519_pub_key.to_vec(),
+        device_seed,
+    };
+
+    log::info!("Exported device seed backup.");
+
+    Ok(serde_json::to_vec(&backup)?)
+}
+
+/// Imports the device seed from the given backup, which must be encrypted with the given passphrase
+/// If a device seed already exists in the keystore, it must be the same as the one in the backup
+pub(crate) async fn restore_device_seed_backup(
+    keystore: &MetaLairClient,
+    backup: &[u8],
+    passphrase: SharedLockedArray,
+) -> crate::Result<()> {
+    let backup: DeviceSeedBackup = serde_json::from_slice(backup)?;
+    if backup.version != DEVICE_SEED_BACKUP_VERSION {
+        return Err(crate::Error::DeviceSeedBackupError(format!(
+            "Unsupported device seed backup version {}",
+            backup.version
+        )));
+    }
+
+    if let Some(device_seed_info) = get_seed_info(keystore, DEVICE_SEED_LAIR_KEYSTORE_TAG).await? {
+        if device_seed_info.ed25519_pub_key.to_vec() != backup.device_seed_pub_key {
+            return Err(device_seed_backup_error(
+                "The keystore already has a different device seed",
+            ));
+        }
+        log::info!("The device seed from the backup was already restored.");
+        return Ok(());
+    }
+
+    let encrypted_seed = backup.device_seed.clone();
+    let seed = tokio::task::spawn_blocking(move || encrypted_seed.decrypt(passphrase))
+        .await
+        .map_err(|err| crate::Error::DeviceSeedBackupError(format!("{err:?}")))??;
+    let seed: Arc<[u8]> = {
+        let mut seed = seed
+            .lock()
+            .map_err(|err| crate::Error::LockError(format!("{err:?}")))?;
+        seed.lock().to_vec().into()
+    };
+
+    let transport_seed_info = get_or_create_transport_seed(keystore).await?;
+    let transport_pub_key = transport_seed_info.x25519_pub_key;
+
+    // Lair only imports seeds encrypted for one of its keys, so have lair box it for itself
+    let (nonce, cipher) = keystore
+        .lair_client()
+        .crypto_box_xsalsa_by_pub_key(
+            transport_pub_key.clone(),
+            transport_pub_key.clone(),
+            None,
+            seed,
+        )
+        .await
+        .map_err(lair_error)?;
+    let device_seed_info = keystore
+        .lair_client()
+        .import_seed(
+            transport_pub_key.clone(),
+            transport_pub_key,
+            None,
+            nonce,
+            cipher,
+            DEVICE_SEED_LAIR_KEYSTORE_TAG.into(),
+            true,
+        )
+        .await
+        .map_err(lair_error)?;
+
+    if device_seed_info.ed25519_pub_key.to_vec() != backup.device_seed_pub_key {
+        return Err(device_seed_backup_error(
+            "The restored device seed does not match the backup",
+        ));
+    }
+
+    log::info!("Restored device seed from backup.");
+
+    Ok(())
+}
@@ -19,7 +19,7 @@ fn read_config(config_path: &std::path::Path) -> LairResult<LairServerConfig> {
     Ok(Arc::new(config))
 }
 
-fn limits() -> PwHashLimits {
+pub(crate) fn limits() -> PwHashLimits {
     if cfg!(any(target_os = "android", target_os = "ios")) {
         PwHashLimits::Interactive
     } else {
@@ -58,6 +58,29 @@ pub fn spawn_lair_keystore_in_proc(
     })
 }
 
+/// Whether the given passphrase unlocks the existing keystore
+/// Spawns the in-process keystore with it, which fails if lair can't decrypt its secrets with the passphrase
+/// Any failure to spawn it is taken as a wrong passphrase, since lair doesn't report it as a distinct error
+pub(crate) async fn keystore_accepts_passphrase(
+    config_path: &std::path::Path,
+    passphrase: SharedLockedArray,
+) -> LairResult<bool> {
+    let config = read_config(config_path)?;
+    let store_factory = create_sql_pool_factory(&config.store_file, &config.database_salt);
+
+    match InProcKeystore::new(config, store_factory, passphrase).await {
+        Ok(in_proc_keystore) => {
+            // Dropping it closes the keystore, so that it can be spawned again for the runtime
+            drop(in_proc_keystore);
+            Ok(true)
+        }
+        Err(err) => {
+            log::warn!("Failed to unlock the keystore: {err:?}");
+            Ok(false)
+        }
+    }
+}
+
 pub async fn get_config(
     config_path: &std::path::Path,
     passphrase: SharedLockedArray,
@@ -97,3 +120,30 @@ pub async fn write_config(
 
     Ok(Arc::new(config))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vec_to_locked;
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn only_accepts_the_passphrase_of_the_keystore() {
+        let dir = tempfile::tempdir().unwrap();
+        let config_path = dir.path().join("lair-keystore-config.yaml");
+        let passphrase = || vec_to_locked(b"right passphrase".to_vec());
+        write_config(&config_path, passphrase()).await.unwrap();
+
+        assert!(
+            !keystore_accepts_passphrase(&config_path, vec_to_locked(b"wrong".to_vec()))
+                .await
+                .unwrap()
+        );
+        assert!(keystore_accepts_passphrase(&config_path, passphrase())
+            .await
+            .unwrap());
+        // The keystore can still be unlocked after it was checked
+        assert!(keystore_accepts_passphrase(&config_path, passphrase())
+            .await
+            .unwrap());
+    }
+}
@@ -0,0 +1,219 @@
+use std::sync::{Arc, Mutex};
+
+use lair_keystore::dependencies::{
+    hc_seed_bundle::SharedLockedArray,
+    sodoken::{self, LockedArray, SizedLockedArray},
+};
+use serde::{Deserialize, Serialize};
+
+use super::keystore::limits;
+use crate::filesystem::FileSystem;
+
+/// Secret data encrypted with a key derived from a passphrase
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub(crate) struct PassphraseEncrypted {
+    salt: Vec<u8>,
+    ops_limit: u32,
+    mem_limit: u32,
+    nonce: Vec<u8>,
+    cipher: Vec<u8>,
+}
+
+const KEYSTORE_PASSPHRASE_LENGTH: usize = 32;
+
+fn passphrase_error<E: std::fmt::Debug>(err: E) -> crate::Error {
+    crate::Error::PassphraseError(format!("{err:?}"))
+}
+
+fn derive_key(
+    passphrase: &SharedLockedArray,
+    salt: &[u8; sodoken::argon2::ARGON2_ID_SALTBYTES],
+    ops_limit: u32,
+    mem_limit: u32,
+) -> crate::Result<SizedLockedArray<{ sodoken::secretbox::XSALSA_KEYBYTES }>> {
+    let mut key = SizedLockedArray::<{ sodoken::secretbox::XSALSA_KEYBYTES }>::new()
+        .map_err(passphrase_error)?;
+    let mut passphrase = passphrase.lock().map_err(passphrase_error)?;
+
+    sodoken::argon2::blocking_argon2id(
+        &mut *key.lock(),
+        &passphrase.lock(),
+        salt,
+        ops_limit,
+        mem_limit,
+    )
+    .map_err(passphrase_error)?;
+
+    Ok(key)
+}
+
+impl PassphraseEncrypted {
+    pub(crate) fn encrypt(
+        passphrase: SharedLockedArray,
+        data: SharedLockedArray,
+    ) -> crate::Result<Self> {
+        let limits = limits();
+        let ops_limit = limits.as_ops_limit();
+        let mem_limit = limits.as_mem_limit();
+
+        let mut salt = [0; sodoken::argon2::ARGON2_ID_SALTBYTES];
+        sodoken::random::randombytes_buf(&mut salt).map_err(passphrase_error)?;
+
+        let mut key = derive_key(&passphrase, &salt, ops_limit, mem_limit)?;
+
+        let mut data = data.lock().map_err(passphrase_error)?;
+        let message = data.lock();
+
+        let mut nonce = [0; sodoken::secretbox::XSALSA_NONCEBYTES];
+        let mut cipher = vec![0; message.len() + sodoken::secretbox::XSALSA_MACBYTES];
+        sodoken::secretbox::xsalsa_easy(&mut nonce, &mut cipher, &message, &key.lock())
+            .map_err(passphrase_error)?;
+
+        Ok(Self {
+            salt: salt.to_vec(),
+            ops_limit,
+            mem_limit,
+            nonce: nonce.to_vec(),
+            cipher,
+        })
+    }
+
+    /// Fails with `Error::WrongPassphrase` if the data was not encrypted with the given passphrase
+    pub(crate) fn decrypt(&self, passphrase: SharedLockedArray) -> crate::Result<SharedLockedArray> {
+        let salt: [u8; sodoken::argon2::ARGON2_ID_SALTBYTES] = self
+            .salt
+            .as_slice()
+            .try_into()
+            .map_err(passphrase_error)?;
+        let nonce: [u8; sodoken::secretbox::XSALSA_NONCEBYTES] = self
+            .nonce
+            .as_slice()
+            .try_into()
+            .map_err(passphrase_error)?;
+        let message_len = self
+            .cipher
+            .len()
+            .checked_sub(sodoken::secretbox::XSALSA_MACBYTES)
+            .ok_or(crate::Error::PassphraseError(String::from(
+                "Malformed passphrase encrypted data",
+            )))?;
+
+        let mut key = derive_key(&passphrase, &salt, self.ops_limit, self.mem_limit)?;
+
+        let mut data = LockedArray::new(message_len).map_err(passphrase_error)?;
+        sodoken::secretbox::xsalsa_open_easy(&mut data.lock(), &self.cipher, &nonce, &key.lock())
+            .map_err(|_err| crate::Error::WrongPassphrase)?;
+
+        Ok(Arc::new(Mutex::new(data)))
+    }
+}
+
+/// The passphrase given by the user is not the one used by lair and the conductor:
+/// it encrypts a random keystore passphrase that is stored in the keystore directory.
+/// This way the user passphrase can be verified before spawning anything, and changed
+/// without re-encrypting the keystore and the conductor databases.
+type WrappedKeystorePassphrase = PassphraseEncrypted;
+
+fn read_wrapped_keystore_passphrase(
+    fs: &FileSystem,
+) -> crate::Result<Option<WrappedKeystorePassphrase>> {
+    let path = fs.keystore_passphrase_path();
+    if !path.exists() {
+        return Ok(None);
+    }
+    let s = std::fs::read_to_string(path)?;
+    let wrapped: WrappedKeystorePassphrase = serde_json::from_str(s.as_str())?;
+    Ok(Some(wrapped))
+}
+
+fn write_wrapped_keystore_passphrase(
+    fs: &FileSystem,
+    wrapped: &WrappedKeystorePassphrase,
+) -> crate::Result<()> {
+    let path = fs.keystore_passphrase_path();
+    let tmp_path = path.with_extension("json.tmp");
+    let data = serde_json::to_string(wrapped)?;
+
+    // Write and rename so that a crash can't leave the keystore without a passphrase file
+    std::fs::write(&tmp_path, data)?;
+    std::fs::rename(tmp_path, path)?;
+
+    Ok(())
+}
+
+/// Unlocks the passphrase for lair and the conductor with the passphrase given by the user
+/// Returns `None` if the keystore passphrase has not been wrapped yet
+pub(crate) fn unlock_keystore_passphrase(
+    fs: &FileSystem,
+    passphrase: SharedLockedArray,
+) -> crate::Result<Option<SharedLockedArray>> {
+    let Some(wrapped) = read_wrapped_keystore_passphrase(fs)? else {
+        return Ok(None);
+    };
+
+    let keystore_passphrase = wrapped.decrypt(passphrase)?;
+    Ok(Some(keystore_passphrase))
+}
+
+/// Generates a random passphrase for a new keystore, and stores it encrypted with the passphrase given by the user
+pub(crate) fn new_keystore_passphrase(
+    fs: &FileSystem,
+    passphrase: SharedLockedArray,
+) -> crate::Result<SharedLockedArray> {
+    let mut keystore_passphrase =
+        LockedArray::new(KEYSTORE_PASSPHRASE_LENGTH).map_err(passphrase_error)?;
+    sodoken::random::randombytes_buf(&mut keystore_passphrase.lock())
+        .map_err(passphrase_error)?;
+    let keystore_passphrase: SharedLockedArray = Arc::new(Mutex::new(keystore_passphrase));
+
+    wrap_keystore_passphrase(fs, passphrase, keystore_passphrase.clone())?;
+
+    Ok(keystore_passphrase)
+}
+
+/// Stores the keystore passphrase encrypted with the passphrase given by the user
+pub(crate) fn wrap_keystore_passphrase(
+    fs: &FileSystem,
+    passphrase: SharedLockedArray,
+    keystore_passphrase: SharedLockedArray,
+) -> crate::Result<()> {
+    let wrapped = WrappedKeystorePassphrase::encrypt(passphrase, keystore_passphrase)?;
+    write_wrapped_keystore_passphrase(fs, &wrapped)?;
+    log::info!("Stored the wrapped keystore passphrase.");
+    Ok(())
+}
+
+/// Whether the given passphrase unlocks the keystore
+pub(crate) fn verify_passphrase(
+    fs: &FileSystem,
+    passphrase: SharedLockedArray,
+) -> crate::Result<bool> {
+    let Some(wrapped) = read_wrapped_keystore_passphrase(fs)? else {
+        return Err(crate::Error::PassphraseError(String::from(
+            "The keystore has not been launched with a passphrase yet",
+        )));
+    };
+
+    match wrapped.decrypt(passphrase) {
+        Ok(_) => Ok(true),
+        Err(crate::Error::WrongPassphrase) => Ok(false),
+        Err(err) => Err(err),
+    }
+}
+
+/// Re-encrypts the keystore passphrase with the new passphrase given by the user
+pub(crate) fn change_passphrase(
+    fs: &FileSystem,
+    old_passphrase: SharedLockedArray,
+    new_passphrase: SharedLockedArray,
+) -> crate::Result<()> {
+    let Some(keystore_passphrase) = unlock_keystore_passphrase(fs, old_passphrase)? else {
+        return Err(crate::Error::PassphraseError(String::from(
+            "The keystore has not been launched with a passphrase yet",
+        )));
+    };
+
+    wrap_keystore_passphrase(fs, new_passphrase, keystore_passphrase)?;
+    log::info!("Changed the keystore passphrase.");
+    Ok(())
+}
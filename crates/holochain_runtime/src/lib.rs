@@ -1,18 +1,44 @@
+mod agent_keys;
+mod backup;
 mod config;
+#[cfg(feature = "dpki")]
+mod dpki;
 mod filesystem;
 mod launch;
 mod holochain_runtime;
 mod error;
 mod happs;
 mod lair_signer;
+#[cfg(feature = "local-servers")]
+mod local_servers;
+mod mdns;
+mod network_profile;
+mod network_status;
+mod peer_info;
+mod shutdown;
+mod supervisor;
+#[cfg(feature = "testing")]
+pub mod testing;
 mod utils;
 
+pub use agent_keys::AgentKey;
+pub use backup::BackupManifest;
 pub use config::*;
+#[cfg(feature = "dpki")]
+pub use dpki::*;
 pub use error::*;
 pub use holochain_runtime::*;
 pub use lair_signer::*;
+#[cfg(feature = "local-servers")]
+pub use local_servers::{LocalServers, LocalServersMode};
+pub use network_profile::NetworkProfile;
+pub use network_status::*;
+pub use shutdown::{ShutdownPhase, DEFAULT_SHUTDOWN_TIMEOUT};
+pub use supervisor::SupervisorEvent;
+pub use mdns::{MdnsDiscovery, MdnsDiscoverySettings, MdnsParticipants, MdnsPeer};
 pub use filesystem::*;
 pub use happs::update::UpdateHappError;
 pub use utils::*;
+pub use launch::DEVICE_SEED_LAIR_KEYSTORE_TAG;
 pub use holochain_conductor_api::conductor::NetworkConfig;
 pub use holochain_conductor_api::ZomeCallParamsSigned;
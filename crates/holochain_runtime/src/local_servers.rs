@@ -0,0 +1,117 @@
+use std::{
+    net::{IpAddr, SocketAddr},
+    sync::{Arc, Mutex},
+};
+
+use kitsune2_bootstrap_srv::{BootstrapSrv, Config};
+use url2::Url2;
+
+/// Upper bound for the worker threads of the bootstrap server, since only a few devices connect to the local servers
+const MAX_WORKER_THREADS: usize = 8;
+
+/// The defaults of the kitsune2 bootstrap server to run the local servers with
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LocalServersMode {
+    /// Prunes the expired agent infos every minute, and waits longer for new requests, which uses less CPU
+    #[default]
+    Production,
+    /// Prunes the expired agent infos every 10 seconds, and handles new requests as soon as possible
+    Testing,
+}
+
+/// Bootstrap and signal servers running in this process, to connect peers without any external infrastructure
+#[derive(Clone)]
+pub struct LocalServers {
+    bootstrap_url: Url2,
+    signal_url: Url2,
+    server: Arc<Mutex<Option<BootstrapSrv>>>,
+}
+
+fn local_servers_error(reason: String) -> crate::Error {
+    crate::Error::LocalServersError(reason)
+}
+
+impl LocalServers {
+    /// Spawns the kitsune2 bootstrap server, which also serves as the signal server, at the given address
+    ///
+    /// * `listen_address` - the address to bind to; use port 0 to pick a free port,
+    ///    and `0.0.0.0` to accept connections from other devices in the local network
+    /// * `mode` - the defaults of the bootstrap server to use
+    pub(crate) async fn spawn(
+        listen_address: SocketAddr,
+        mode: LocalServersMode,
+    ) -> crate::Result<Self> {
+        let mut config = match mode {
+            LocalServersMode::Production => Config::production(),
+            LocalServersMode::Testing => Config::testing(),
+        };
+        config.worker_thread_count = config.worker_thread_count.min(MAX_WORKER_THREADS);
+        config.listen_address_list = vec![listen_address];
+
+        // The server runs its own runtime in background threads, so it can't be created from an async task
+        let server = tokio::task::spawn_blocking(move || BootstrapSrv::new(config))
+            .await
+            .map_err(|err| local_servers_error(format!("{err:?}")))??;
+
+        let bound_address = server
+            .listen_addrs()
+            .first()
+            .cloned()
+            .ok_or(local_servers_error(String::from(
+                "The server is not listening at any address",
+            )))?;
+
+        // Other devices can't connect to an unspecified address, so advertise the local IP instead
+        let ip = if bound_address.ip().is_unspecified() {
+            local_ip_address::local_ip().map_err(|err| local_servers_error(format!("{err:?}")))?
+        } else {
+            bound_address.ip()
+        };
+        let host = match ip {
+            IpAddr::V4(ip) => ip.to_string(),
+            IpAddr::V6(ip) => format!("[{ip}]"),
+        };
+        let port = bound_address.port();
+
+        let local_servers = Self {
+            bootstrap_url: url2::url2!("http://{host}:{port}"),
+            signal_url: url2::url2!("ws://{host}:{port}"),
+            server: Arc::new(Mutex::new(Some(server))),
+        };
+
+        log::info!(
+            "Spawned local bootstrap server at {} and signal server at {}.",
+            local_servers.bootstrap_url,
+            local_servers.signal_url
+        );
+
+        Ok(local_servers)
+    }
+
+    /// The URL of the local bootstrap server
+    pub fn bootstrap_url(&self) -> Url2 {
+        self.bootstrap_url.clone()
+    }
+
+    /// The URL of the local signal server
+    pub fn signal_url(&self) -> Url2 {
+        self.signal_url.clone()
+    }
+
+    /// Stops the servers
+    pub(crate) fn shutdown(&self) -> crate::Result<()> {
+        let server = self
+            .server
+            .lock()
+            .map_err(|err| crate::Error::LockError(format!("{err:?}")))?
+            .take();
+
+        if let Some(server) = server {
+            // Dropping the server shuts it down, which blocks until its threads have finished
+            std::thread::spawn(move || drop(server));
+            log::info!("Stopped the local bootstrap and signal servers.");
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,508 @@
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    sync::{atomic::AtomicBool, Arc, Mutex},
+    time::Duration,
+};
+
+use async_std::stream::StreamExt;
+use base64::Engine;
+use holochain::prelude::{DnaHash, DnaHashB64, Timestamp};
+use holochain_client::{AdminWebsocket, AgentPubKey, AppInfo, InstalledAppId};
+use holochain_conductor_api::CellInfo;
+use kitsune2_api::{AgentId, AgentInfoSigned, K2Error, SpaceId};
+use kitsune2_core::Ed25519Verifier;
+use kitsune_p2p_mdns::{mdns_create_broadcast_thread, mdns_kill_thread, mdns_listen};
+use serde::{Deserialize, Serialize};
+use tokio::task::JoinHandle;
+
+use crate::NetworkProfile;
+
+/// Interval between checks for changes in the agent infos of the local agents
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Maximum interval between retries when the admin websocket fails
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+/// How long a discovered peer is kept after its last broadcast was received
+const PEER_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
+/// A peer that was discovered via mDNS
+#[derive(Debug, Clone)]
+pub struct MdnsPeer {
+    pub agent_info: Arc<AgentInfoSigned>,
+    /// When the last broadcast for this peer was received
+    pub last_seen: Timestamp,
+}
+
+/// Which apps take part in the mDNS discovery
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub enum MdnsParticipants {
+    /// All the installed apps
+    AllApps,
+    /// Only the given apps, and the given DNAs in any app
+    Only {
+        app_ids: BTreeSet<InstalledAppId>,
+        dna_hashes: BTreeSet<DnaHashB64>,
+    },
+}
+
+/// The mDNS discovery settings, persisted across restarts
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct MdnsDiscoverySettings {
+    pub enabled: bool,
+    pub participants: MdnsParticipants,
+}
+
+impl MdnsDiscoverySettings {
+    fn participating_spaces(&self, apps: &Vec<AppInfo>) -> Option<HashSet<Vec<u8>>> {
+        let MdnsParticipants::Only {
+            app_ids,
+            dna_hashes,
+        } = &self.participants
+        else {
+            return None;
+        };
+
+        let mut spaces: HashSet<Vec<u8>> = dna_hashes
+            .iter()
+            .map(|dna_hash| space_id_bytes(&DnaHash::from(dna_hash.clone())))
+            .collect();
+
+        for app in apps {
+            if !app_ids.contains(&app.installed_app_id) {
+                continue;
+            }
+            for cell in app.cell_info.values().flatten() {
+                match cell {
+                    CellInfo::Provisioned(c) => {
+                        spaces.insert(space_id_bytes(c.cell_id.dna_hash()));
+                    }
+                    CellInfo::Cloned(c) => {
+                        spaces.insert(space_id_bytes(c.cell_id.dna_hash()));
+                    }
+                    CellInfo::Stem(_) => {}
+                }
+            }
+        }
+
+        Some(spaces)
+    }
+}
+
+#[derive(Default)]
+struct MdnsDiscoveryState {
+    /// The encoded agent info and the handle of the broadcast thread for each local agent
+    broadcasts: HashMap<(SpaceId, AgentId), (String, Arc<AtomicBool>)>,
+    /// The tasks listening to broadcasts in each space
+    listeners: HashMap<SpaceId, JoinHandle<()>>,
+    discovered_peers: HashMap<(SpaceId, AgentId), MdnsPeer>,
+    poll_task: Option<JoinHandle<()>>,
+}
+
+impl MdnsDiscoveryState {
+    /// Forgets the peers that haven't broadcasted for `PEER_TIMEOUT`, whose agent info has expired,
+    /// or whose space is not listened to anymore
+    fn evict_stale_peers(&mut self) {
+        let now = Timestamp::now().as_micros();
+        let timeout = PEER_TIMEOUT.as_micros() as i64;
+        let listeners = &self.listeners;
+        self.discovered_peers.retain(|(space, _), peer| {
+            listeners.contains_key(space)
+                && now - peer.last_seen.as_micros() < timeout
+                && now < peer.agent_info.expires_at.as_micros()
+        });
+    }
+
+    fn stop(&mut self) {
+        if let Some(poll_task) = self.poll_task.take() {
+            poll_task.abort();
+        }
+        for (_, listener) in self.listeners.drain() {
+            listener.abort();
+        }
+        for (_, (_, handle)) in self.broadcasts.drain() {
+            mdns_kill_thread(handle);
+        }
+        self.discovered_peers.clear();
+    }
+}
+
+/// Broadcasts the agent infos of the local agents in the LAN via mDNS,
+/// and adds the agent infos broadcasted by other peers in the same spaces to the conductor
+/// It can be enabled and disabled at runtime, and restricted to some of the apps or DNAs
+#[derive(Clone)]
+pub struct MdnsDiscovery {
+    admin_port: u16,
+    settings: Arc<Mutex<MdnsDiscoverySettings>>,
+    network_profile: Arc<Mutex<NetworkProfile>>,
+    state: Arc<Mutex<MdnsDiscoveryState>>,
+}
+
+fn base64_encode(bytes: &[u8]) -> String {
+    base64::prelude::BASE64_URL_SAFE_NO_PAD.encode(bytes)
+}
+
+fn decode_agent_infos(encoded_agent_infos: Vec<String>) -> Vec<Arc<AgentInfoSigned>> {
+    encoded_agent_infos
+        .iter()
+        .filter_map(|agent_info| {
+            AgentInfoSigned::decode(&Ed25519Verifier, agent_info.as_bytes()).ok()
+        })
+        .collect()
+}
+
+/// Kitsune agent ids are the raw ed25519 public keys of the agents
+fn agent_id_bytes(agent_pub_key: &AgentPubKey) -> Vec<u8> {
+    agent_pub_key.get_raw_32().to_vec()
+}
+
+/// Kitsune space ids are the raw hashes of the DNAs
+fn space_id_bytes(dna_hash: &DnaHash) -> Vec<u8> {
+    dna_hash.get_raw_32().to_vec()
+}
+
+impl MdnsDiscovery {
+    /// Creates the mDNS discovery service for the conductor listening at the given admin port,
+    /// starting it right away if it's enabled in the given settings or forced by the network profile
+    pub(crate) async fn new(
+        admin_port: u16,
+        settings: MdnsDiscoverySettings,
+        network_profile: NetworkProfile,
+    ) -> crate::Result<Self> {
+        let mdns_discovery = Self {
+            admin_port,
+            settings: Arc::new(Mutex::new(settings)),
+            network_profile: Arc::new(Mutex::new(network_profile)),
+            state: Arc::new(Mutex::new(MdnsDiscoveryState::default())),
+        };
+
+        if mdns_discovery.is_enabled()? {
+            mdns_discovery.start().await?;
+        }
+
+        Ok(mdns_discovery)
+    }
+
+    /// Whether the discovery should be running, given its settings and the network profile
+    pub fn is_enabled(&self) -> crate::Result<bool> {
+        let network_profile = self
+            .network_profile
+            .lock()
+            .map_err(|err| crate::Error::LockError(format!("{err:?}")))?
+            .clone();
+
+        match network_profile.mdns_discovery_override() {
+            Some(enabled) => Ok(enabled),
+            None => Ok(self.settings()?.enabled),
+        }
+    }
+
+    /// Starts or stops the discovery if the new network profile requires it
+    pub(crate) async fn set_network_profile(
+        &self,
+        network_profile: NetworkProfile,
+    ) -> crate::Result<()> {
+        {
+            let mut current_network_profile = self
+                .network_profile
+                .lock()
+                .map_err(|err| crate::Error::LockError(format!("{err:?}")))?;
+            *current_network_profile = network_profile;
+        }
+
+        self.start_or_stop().await
+    }
+
+    async fn start_or_stop(&self) -> crate::Result<()> {
+        if self.is_enabled()? {
+            self.start().await
+        } else {
+            self.shutdown()
+        }
+    }
+
+    async fn start(&self) -> crate::Result<()> {
+        if self.lock_state()?.poll_task.is_some() {
+            return Ok(());
+        }
+
+        let admin_ws = Arc::new(wait_until_admin_ws_is_available(self.admin_port).await?);
+
+        let poll_task = tokio::spawn({
+            let mdns_discovery = self.clone();
+            async move {
+                let mut backoff = POLL_INTERVAL;
+                loop {
+                    match mdns_discovery.sync_local_agents(&admin_ws).await {
+                        Ok(()) => {
+                            backoff = POLL_INTERVAL;
+                        }
+                        Err(err) => {
+                            log::error!("Error updating the mDNS broadcasts: {err:?}.");
+                            backoff = (backoff * 2).min(MAX_BACKOFF);
+                        }
+                    }
+                    async_std::task::sleep(backoff).await;
+                }
+            }
+        });
+
+        let mut state = self.lock_state()?;
+        if state.poll_task.is_some() {
+            // Started concurrently
+            poll_task.abort();
+        } else {
+            state.poll_task = Some(poll_task);
+            log::info!("Started mDNS discovery.");
+        }
+
+        Ok(())
+    }
+
+    fn lock_state(&self) -> crate::Result<std::sync::MutexGuard<'_, MdnsDiscoveryState>> {
+        self.state
+            .lock()
+            .map_err(|err| crate::Error::LockError(format!("{err:?}")))
+    }
+
+    /// The current settings
+    pub fn settings(&self) -> crate::Result<MdnsDiscoverySettings> {
+        let settings = self
+            .settings
+            .lock()
+            .map_err(|err| crate::Error::LockError(format!("{err:?}")))?;
+        Ok(settings.clone())
+    }
+
+    /// Applies the given settings, starting or stopping the discovery if needed
+    /// The participants are applied on the next check for changes in the local agents
+    pub(crate) async fn apply_settings(
+        &self,
+        settings: MdnsDiscoverySettings,
+    ) -> crate::Result<()> {
+        {
+            let mut current_settings = self
+                .settings
+                .lock()
+                .map_err(|err| crate::Error::LockError(format!("{err:?}")))?;
+            *current_settings = settings;
+        }
+
+        self.start_or_stop().await
+    }
+
+    /// The peers discovered via mDNS that broadcasted recently
+    pub fn discovered_peers(&self) -> crate::Result<Vec<MdnsPeer>> {
+        let mut state = self.lock_state()?;
+        state.evict_stale_peers();
+        Ok(state.discovered_peers.values().cloned().collect())
+    }
+
+    /// Broadcasts the agent infos for the local agents that changed since the last check,
+    /// and starts listening in the spaces that have new local agents
+    async fn sync_local_agents(&self, admin_ws: &Arc<AdminWebsocket>) -> crate::Result<()> {
+        let apps = admin_ws.list_apps(None).await?;
+        let local_agents: HashSet<Vec<u8>> = apps
+            .iter()
+            .map(|app| agent_id_bytes(&app.agent_pub_key))
+            .collect();
+
+        let participating_spaces = self.settings()?.participating_spaces(&apps);
+
+        let agent_infos = decode_agent_infos(admin_ws.agent_info(None).await?);
+        let local_agent_infos: Vec<Arc<AgentInfoSigned>> = agent_infos
+            .into_iter()
+            .filter(|agent_info| local_agents.contains(&agent_info.agent[..].to_vec()))
+            .filter(|agent_info| match &participating_spaces {
+                Some(spaces) => spaces.contains(&agent_info.space[..].to_vec()),
+                None => true,
+            })
+            .collect();
+
+        let mut state = self.lock_state()?;
+        if state.poll_task.is_none() {
+            // Stopped while the agent infos were being fetched
+            return Ok(());
+        }
+
+        let current_cells: HashSet<(SpaceId, AgentId)> = local_agent_infos
+            .iter()
+            .map(|agent_info| (agent_info.space.clone(), agent_info.agent.clone()))
+            .collect();
+        let current_spaces: HashSet<SpaceId> = current_cells
+            .iter()
+            .map(|(space, _)| space.clone())
+            .collect();
+
+        // Stop broadcasting the agents that left their space or no longer participate
+        state.broadcasts.retain(|cell, (_, handle)| {
+            let keep = current_cells.contains(cell);
+            if !keep {
+                mdns_kill_thread(handle.clone());
+            }
+            keep
+        });
+        state.listeners.retain(|space, listener| {
+            let keep = current_spaces.contains(space);
+            if !keep {
+                listener.abort();
+            }
+            keep
+        });
+        state.evict_stale_peers();
+
+        for agent_info in local_agent_infos {
+            let cell = (agent_info.space.clone(), agent_info.agent.clone());
+            let encoded_agent_info = agent_info.encode()?;
+
+            if let Some((broadcasted_agent_info, handle)) = state.broadcasts.get(&cell) {
+                if broadcasted_agent_info.eq(&encoded_agent_info) {
+                    continue;
+                }
+                mdns_kill_thread(handle.clone());
+            }
+
+            // Broadcast by using Space as service type and Agent as service name
+            let space_b64 = base64_encode(&agent_info.space[..]);
+            let agent_b64 = base64_encode(&agent_info.agent[..]);
+
+            log::debug!(
+                "Broadcasting mDNS agent {agent_b64} in space {space_b64} with URL {:?}.",
+                agent_info.url
+            );
+            let handle =
+                mdns_create_broadcast_thread(space_b64, agent_b64, encoded_agent_info.as_bytes());
+            state.broadcasts.insert(cell, (encoded_agent_info, handle));
+
+            if !state.listeners.contains_key(&agent_info.space) {
+                let listener =
+                    self.spawn_listen_to_space_task(agent_info.space.clone(), admin_ws.clone());
+                state.listeners.insert(agent_info.space.clone(), listener);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn spawn_listen_to_space_task(
+        &self,
+        space: SpaceId,
+        admin_ws: Arc<AdminWebsocket>,
+    ) -> JoinHandle<()> {
+        let mdns_discovery = self.clone();
+        let space_b64 = base64_encode(&space[..]);
+
+        tokio::spawn(async move {
+            log::debug!("Listening for mDNS agents for space {space_b64}.");
+            let stream = mdns_listen(space_b64);
+            tokio::pin!(stream);
+            while let Some(maybe_response) = stream.next().await {
+                let response = match maybe_response {
+                    Ok(response) => response,
+                    Err(e) => {
+                        log::error!("Failed to get peers from mDNS {:?}", e);
+                        continue;
+                    }
+                };
+                log::debug!(
+                    "Peer found via mDNS with service type {}, service name {} and address {}.",
+                    response.service_type,
+                    response.service_name,
+                    response.addr
+                );
+                let maybe_agent_info_signed: Result<Arc<AgentInfoSigned>, K2Error> =
+                    AgentInfoSigned::decode(&Ed25519Verifier, response.buffer.as_slice());
+                let Ok(remote_agent_info_signed) = maybe_agent_info_signed else {
+                    log::error!("Failed to decode mDNS peer {:?}", maybe_agent_info_signed);
+                    continue;
+                };
+
+                match mdns_discovery.peer_discovered(remote_agent_info_signed.clone()) {
+                    Ok(false) => continue,
+                    Ok(true) => {}
+                    Err(err) => {
+                        log::error!("Failed to store mDNS peer {err:?}");
+                        continue;
+                    }
+                }
+
+                let result = remote_agent_info_signed.encode();
+                let Ok(encoded_agent_info) = result else {
+                    log::error!("Failed to encode agent info: {result:?}");
+                    continue;
+                };
+                log::info!("Adding agent info {encoded_agent_info:?}");
+                if let Err(e) = admin_ws.add_agent_info(vec![encoded_agent_info]).await {
+                    log::error!("Failed to store mDNS peer {:?}", e);
+                }
+            }
+        })
+    }
+
+    /// Records the broadcast of a peer
+    /// Returns whether its agent info needs to be added to the conductor, which is only the case
+    /// for remote peers whose agent info is newer than the one received last
+    fn peer_discovered(&self, agent_info: Arc<AgentInfoSigned>) -> crate::Result<bool> {
+        let mut state = self.lock_state()?;
+        let cell = (agent_info.space.clone(), agent_info.agent.clone());
+
+        if state.broadcasts.contains_key(&cell) {
+            return Ok(false);
+        }
+
+        let is_new = match state.discovered_peers.get(&cell) {
+            Some(peer) => peer.agent_info.created_at < agent_info.created_at,
+            None => true,
+        };
+        let agent_info = if is_new {
+            agent_info
+        } else {
+            state.discovered_peers[&cell].agent_info.clone()
+        };
+        state.discovered_peers.insert(
+            cell,
+            MdnsPeer {
+                agent_info,
+                last_seen: Timestamp::now(),
+            },
+        );
+
+        Ok(is_new)
+    }
+
+    /// Stops broadcasting and listening
+    pub(crate) fn shutdown(&self) -> crate::Result<()> {
+        let mut state = self.lock_state()?;
+        if state.poll_task.is_none() {
+            return Ok(());
+        }
+        state.stop();
+
+        log::info!("Stopped mDNS discovery.");
+
+        Ok(())
+    }
+}
+
+async fn wait_until_admin_ws_is_available(admin_port: u16) -> crate::Result<AdminWebsocket> {
+    let mut retry_count = 0;
+    loop {
+        let connect_result = AdminWebsocket::connect(format!("localhost:{}", admin_port)).await;
+        match connect_result {
+            Ok(admin_ws) => {
+                return Ok(admin_ws);
+            }
+            Err(err) => {
+                log::error!("Could not connect to the admin interface: {}", err);
+                async_std::task::sleep(Duration::from_millis(200)).await;
+
+                retry_count += 1;
+                if retry_count == 200 {
+                    return Err(crate::Error::AdminWebsocketError(
+                        "Can't connect to holochain".to_string(),
+                    ));
+                }
+            }
+        }
+    }
+}
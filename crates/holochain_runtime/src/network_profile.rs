@@ -0,0 +1,72 @@
+use holochain_conductor_api::conductor::NetworkConfig;
+use serde::{Deserialize, Serialize};
+
+/// Holochain requires a bootstrap and a signal URL even when they are not used,
+/// so the profiles without them point to port 0 in the loopback interface, to which no connection can be made
+const UNUSED_BOOTSTRAP_URL: &'static str = "http://127.0.0.1:0";
+const UNUSED_SIGNAL_URL: &'static str = "ws://127.0.0.1:0";
+
+/// How the conductor connects to other peers
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub enum NetworkProfile {
+    /// Connect through the bootstrap and signal servers in `HolochainRuntimeConfig::network_config`
+    #[default]
+    Wan,
+    /// Only connect to peers in the local network discovered via mDNS, without any bootstrap or signal server
+    LanOnly,
+    /// Don't connect to any peer
+    Offline,
+}
+
+impl NetworkProfile {
+    /// Whether the conductor connects to the bootstrap and signal servers with this profile
+    pub fn uses_bootstrap_and_signal(&self) -> bool {
+        match self {
+            NetworkProfile::Wan => true,
+            NetworkProfile::LanOnly | NetworkProfile::Offline => false,
+        }
+    }
+
+    /// The network config for the conductor when running with this profile
+    ///
+    /// Holochain can't disable the bootstrap module outside of its tests, so for the profiles that don't use
+    /// the bootstrap and signal servers, their URLs are replaced by unreachable ones and the bootstrap module
+    /// is configured to wait as long as it can between its retries
+    ///
+    /// * `wan_network_config` - the network config to use with the `Wan` profile
+    pub(crate) fn network_config(&self, wan_network_config: &NetworkConfig) -> NetworkConfig {
+        let mut network_config = wan_network_config.clone();
+
+        if self.uses_bootstrap_and_signal() {
+            return network_config;
+        }
+
+        network_config.bootstrap_url = url2::Url2::parse(UNUSED_BOOTSTRAP_URL);
+        network_config.signal_url = url2::Url2::parse(UNUSED_SIGNAL_URL);
+
+        let advanced = network_config
+            .advanced
+            .get_or_insert_with(|| serde_json::json!({}));
+        if let Some(advanced) = advanced.as_object_mut() {
+            let core_bootstrap = advanced
+                .entry("coreBootstrap")
+                .or_insert_with(|| serde_json::json!({}));
+            if let Some(core_bootstrap) = core_bootstrap.as_object_mut() {
+                core_bootstrap.insert("backoffMinMs".into(), u32::MAX.into());
+                core_bootstrap.insert("backoffMaxMs".into(), u32::MAX.into());
+            }
+        }
+
+        network_config
+    }
+
+    /// Whether this profile forces mDNS discovery to be enabled or disabled,
+    /// regardless of `HolochainRuntime::set_mdns_discovery_enabled()`
+    pub(crate) fn mdns_discovery_override(&self) -> Option<bool> {
+        match self {
+            NetworkProfile::Wan => None,
+            NetworkProfile::LanOnly => Some(true),
+            NetworkProfile::Offline => Some(false),
+        }
+    }
+}
@@ -0,0 +1,191 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    sync::Arc,
+    time::Duration,
+};
+
+use base64::Engine;
+use holochain::prelude::{DnaHash, DnaHashB64, RoleName, Timestamp};
+use holochain_client::InstalledAppId;
+use holochain_conductor_api::CellInfo;
+use kitsune2_api::{AgentInfoSigned, TransportStats};
+use kitsune2_core::Ed25519Verifier;
+use serde::{Deserialize, Serialize};
+use url2::Url2;
+
+use crate::HolochainRuntime;
+
+/// How long to wait for the bootstrap and signal servers to accept a connection
+const REACHABILITY_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// How a peer was found
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub enum PeerSource {
+    /// Broadcasted in the local network via mDNS
+    Mdns,
+    /// Found via the bootstrap server or gossip
+    Bootstrap,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct PeerStatus {
+    /// The base64 encoded kitsune agent id of the peer
+    pub agent: String,
+    /// The URL at which the peer can be reached, `None` if the peer is offline
+    pub url: Option<String>,
+    pub source: PeerSource,
+    pub expires_at: Timestamp,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct CellNetworkStatus {
+    pub dna_hash: DnaHashB64,
+    /// The remote peers known for the DNA, excluding the local agents
+    pub peers: Vec<PeerStatus>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct AppNetworkStatus {
+    pub installed_app_id: InstalledAppId,
+    /// The status for each of the cells of the app, by their role
+    pub roles: BTreeMap<RoleName, Vec<CellNetworkStatus>>,
+}
+
+/// A snapshot of the state of the network for all the apps
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct NetworkStatus {
+    pub apps: Vec<AppNetworkStatus>,
+    /// Whether the bootstrap server accepted a connection, always `false` with the network profiles that don't use it
+    pub bootstrap_reachable: bool,
+    /// Whether the signal server accepted a connection, always `false` with the network profiles that don't use it
+    pub signal_reachable: bool,
+    /// The connections of the transport, as reported by `dump_network_stats`
+    pub transport_stats: TransportStats,
+}
+
+fn space_id_bytes(dna_hash: &DnaHash) -> Vec<u8> {
+    dna_hash.get_raw_32().to_vec()
+}
+
+async fn is_reachable(url: &Url2) -> bool {
+    let (Some(host), Some(port)) = (url.host_str(), url.port_or_known_default()) else {
+        return false;
+    };
+
+    matches!(
+        tokio::time::timeout(
+            REACHABILITY_TIMEOUT,
+            tokio::net::TcpStream::connect((host, port))
+        )
+        .await,
+        Ok(Ok(_))
+    )
+}
+
+impl HolochainRuntime {
+    /// Gets the peers known for each of the cells of every app, the reachability of the bootstrap and signal servers,
+    /// and the stats for the transport connections
+    pub async fn network_status(&self) -> crate::Result<NetworkStatus> {
+        let admin_ws = self.admin_websocket().await?;
+
+        let apps = admin_ws.list_apps(None).await?;
+        let transport_stats = admin_ws.dump_network_stats().await?;
+        let agent_infos: Vec<Arc<AgentInfoSigned>> = admin_ws
+            .agent_info(None)
+            .await?
+            .iter()
+            .filter_map(|agent_info| {
+                AgentInfoSigned::decode(&Ed25519Verifier, agent_info.as_bytes()).ok()
+            })
+            .collect();
+
+        let local_agents: HashSet<Vec<u8>> = apps
+            .iter()
+            .map(|app| app.agent_pub_key.get_raw_32().to_vec())
+            .collect();
+        let mdns_peers: HashSet<(Vec<u8>, Vec<u8>)> = self
+            .mdns_discovery
+            .discovered_peers()?
+            .into_iter()
+            .map(|peer| {
+                (
+                    peer.agent_info.space[..].to_vec(),
+                    peer.agent_info.agent[..].to_vec(),
+                )
+            })
+            .collect();
+
+        let mut peers_by_space: HashMap<Vec<u8>, Vec<PeerStatus>> = HashMap::new();
+        for agent_info in agent_infos {
+            let space = agent_info.space[..].to_vec();
+            let agent = agent_info.agent[..].to_vec();
+            if local_agents.contains(&agent) {
+                continue;
+            }
+
+            let source = if mdns_peers.contains(&(space.clone(), agent.clone())) {
+                PeerSource::Mdns
+            } else {
+                PeerSource::Bootstrap
+            };
+            peers_by_space.entry(space).or_default().push(PeerStatus {
+                agent: base64::prelude::BASE64_URL_SAFE_NO_PAD.encode(&agent),
+                url: agent_info.url.as_ref().map(|url| url.to_string()),
+                source,
+                expires_at: Timestamp(agent_info.expires_at.as_micros()),
+            });
+        }
+
+        let apps = apps
+            .into_iter()
+            .map(|app| {
+                let roles = app
+                    .cell_info
+                    .into_iter()
+                    .map(|(role_name, cells)| {
+                        let cells = cells
+                            .into_iter()
+                            .filter_map(|cell| match cell {
+                                CellInfo::Provisioned(c) => Some(c.cell_id.dna_hash().clone()),
+                                CellInfo::Cloned(c) => Some(c.cell_id.dna_hash().clone()),
+                                CellInfo::Stem(_) => None,
+                            })
+                            .map(|dna_hash| CellNetworkStatus {
+                                peers: peers_by_space
+                                    .get(&space_id_bytes(&dna_hash))
+                                    .cloned()
+                                    .unwrap_or_default(),
+                                dna_hash: dna_hash.into(),
+                            })
+                            .collect();
+                        (role_name, cells)
+                    })
+                    .collect();
+                AppNetworkStatus {
+                    installed_app_id: app.installed_app_id,
+                    roles,
+                }
+            })
+            .collect();
+
+        // The servers are not probed with the network profiles that don't use them
+        let (bootstrap_reachable, signal_reachable) =
+            if self.network_profile().await.uses_bootstrap_and_signal() {
+                let conductor_handle = self.conductor_handle().await;
+                let network_config = &conductor_handle.get_config().network;
+                tokio::join!(
+                    is_reachable(&network_config.bootstrap_url),
+                    is_reachable(&network_config.signal_url)
+                )
+            } else {
+                (false, false)
+            };
+
+        Ok(NetworkStatus {
+            apps,
+            bootstrap_reachable,
+            signal_reachable,
+            transport_stats,
+        })
+    }
+}
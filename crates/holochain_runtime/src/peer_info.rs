@@ -0,0 +1,190 @@
+use std::{
+    collections::HashSet,
+    io::{Read, Write},
+    sync::Arc,
+};
+
+use base64::Engine;
+use flate2::{read::DeflateDecoder, write::DeflateEncoder, Compression};
+use holochain_client::{AppInfo, InstalledAppId};
+use holochain_conductor_api::CellInfo;
+use holochain_types::prelude::DnaHash;
+use kitsune2_api::{AgentInfoSigned, Timestamp};
+use kitsune2_core::Ed25519Verifier;
+use serde::{Deserialize, Serialize};
+
+use crate::HolochainRuntime;
+
+const PEER_INFO_VERSION: u32 = 1;
+
+/// Upper bound for the decompressed size of an imported peer info, so that a small string can't expand without limit
+const MAX_PEER_INFO_SIZE: u64 = 1024 * 1024;
+
+/// An `AgentInfoSigned` split into the canonical encoding of its agent info and the signature over it,
+/// which takes less space than its own encoding with the signature in base64
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct SignedAgentInfo {
+    agent_info: String,
+    #[serde(with = "serde_bytes")]
+    signature: Vec<u8>,
+}
+
+impl SignedAgentInfo {
+    fn from_agent_info_signed(agent_info: &AgentInfoSigned) -> Self {
+        Self {
+            agent_info: agent_info.get_encoded().to_string(),
+            signature: agent_info.get_signature().to_vec(),
+        }
+    }
+
+    /// Decodes the `AgentInfoSigned` from its canonical encoding, which verifies its signature
+    fn decode(&self) -> crate::Result<(String, Arc<AgentInfoSigned>)> {
+        let encoded = serde_json::to_string(&serde_json::json!({
+            "agentInfo": self.agent_info,
+            "signature": base64::prelude::BASE64_URL_SAFE_NO_PAD.encode(&self.signature),
+        }))?;
+        let agent_info = AgentInfoSigned::decode(&Ed25519Verifier, encoded.as_bytes())?;
+        Ok((encoded, agent_info))
+    }
+}
+
+/// The agent infos of the local agents for an app, to be shared out of band with other peers
+///
+/// The bundle itself is not signed: each agent info is signed by its agent and verified on import,
+/// so a tampered bundle can only drop or reorder agent infos, not forge them
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct PeerInfo {
+    version: u32,
+    agent_infos: Vec<SignedAgentInfo>,
+}
+
+impl PeerInfo {
+    /// MessagePack, compressed with deflate and encoded in base64url
+    fn encode(&self) -> crate::Result<String> {
+        let bytes = rmp_serde::to_vec(self).map_err(|err| peer_info_error(format!("{err:?}")))?;
+        let mut encoder = DeflateEncoder::new(Vec::new(), Compression::best());
+        encoder.write_all(&bytes)?;
+        let compressed = encoder.finish()?;
+        Ok(base64::prelude::BASE64_URL_SAFE_NO_PAD.encode(compressed))
+    }
+
+    fn decode(peer_info: &str) -> crate::Result<Self> {
+        let compressed = base64::prelude::BASE64_URL_SAFE_NO_PAD
+            .decode(peer_info.trim())
+            .map_err(|err| peer_info_error(format!("{err:?}")))?;
+        let mut bytes = Vec::new();
+        DeflateDecoder::new(compressed.as_slice())
+            .take(MAX_PEER_INFO_SIZE)
+            .read_to_end(&mut bytes)
+            .map_err(|err| peer_info_error(format!("{err:?}")))?;
+        rmp_serde::from_slice(&bytes).map_err(|err| peer_info_error(format!("{err:?}")))
+    }
+}
+
+fn peer_info_error(reason: String) -> crate::Error {
+    crate::Error::PeerInfoError(reason)
+}
+
+fn app_dna_hashes(app: &AppInfo) -> Vec<DnaHash> {
+    app.cell_info
+        .values()
+        .flatten()
+        .filter_map(|cell| match cell {
+            CellInfo::Provisioned(c) => Some(c.cell_id.dna_hash().clone()),
+            CellInfo::Cloned(c) => Some(c.cell_id.dna_hash().clone()),
+            CellInfo::Stem(_) => None,
+        })
+        .collect()
+}
+
+impl HolochainRuntime {
+    /// Exports the agent infos of the local agent in all the cells of the given app,
+    /// as a compressed base64url string compact enough to be shared as a QR code
+    /// Each agent info is signed by the agent, but the exported string as a whole is not
+    /// Another peer can import it with `Self::import_peer_info()` to connect to this agent without a bootstrap server
+    ///
+    /// * `app_id` - the app whose agent infos to export
+    pub async fn export_peer_info(&self, app_id: InstalledAppId) -> crate::Result<String> {
+        let admin_ws = self.admin_websocket().await?;
+        let apps = admin_ws.list_apps(None).await?;
+        let app = apps
+            .into_iter()
+            .find(|app| app.installed_app_id.eq(&app_id))
+            .ok_or(crate::Error::AppDoesNotExist(app_id.clone()))?;
+
+        let local_agent = app.agent_pub_key.get_raw_32().to_vec();
+        let encoded_agent_infos = admin_ws.agent_info(Some(app_dna_hashes(&app))).await?;
+
+        let agent_infos: Vec<SignedAgentInfo> = encoded_agent_infos
+            .into_iter()
+            .filter_map(|encoded_agent_info| {
+                AgentInfoSigned::decode(&Ed25519Verifier, encoded_agent_info.as_bytes()).ok()
+            })
+            .filter(|agent_info| agent_info.agent[..].to_vec().eq(&local_agent))
+            .map(|agent_info| SignedAgentInfo::from_agent_info_signed(&agent_info))
+            .collect();
+
+        if agent_infos.is_empty() {
+            return Err(peer_info_error(format!(
+                "App {app_id} has not joined any network yet"
+            )));
+        }
+
+        let peer_info = PeerInfo {
+            version: PEER_INFO_VERSION,
+            agent_infos,
+        };
+
+        peer_info.encode()
+    }
+
+    /// Imports the agent infos exported by another peer with `Self::export_peer_info()`
+    /// Checks the signature of every agent info, and skips the ones that have expired
+    /// or that are for DNAs that are not installed in this conductor
+    /// Returns the number of agent infos that were added to the conductor
+    ///
+    /// * `peer_info` - the string exported by the other peer
+    pub async fn import_peer_info(&self, peer_info: String) -> crate::Result<usize> {
+        let peer_info = PeerInfo::decode(&peer_info)?;
+        if peer_info.version != PEER_INFO_VERSION {
+            return Err(peer_info_error(format!(
+                "Unsupported peer info version {}",
+                peer_info.version
+            )));
+        }
+
+        let admin_ws = self.admin_websocket().await?;
+        let apps = admin_ws.list_apps(None).await?;
+        let local_spaces: HashSet<Vec<u8>> = apps
+            .iter()
+            .flat_map(app_dna_hashes)
+            .map(|dna_hash| dna_hash.get_raw_32().to_vec())
+            .collect();
+
+        let now = Timestamp::now();
+        let mut valid_agent_infos: Vec<String> = Vec::new();
+        for signed_agent_info in peer_info.agent_infos {
+            let (encoded_agent_info, agent_info) = signed_agent_info.decode()?;
+
+            if agent_info.expires_at < now {
+                log::warn!("Skipping expired agent info {agent_info:?}.");
+                continue;
+            }
+            if !local_spaces.contains(&agent_info.space[..].to_vec()) {
+                log::warn!("Skipping agent info for a space that is not installed {agent_info:?}.");
+                continue;
+            }
+
+            valid_agent_infos.push(encoded_agent_info);
+        }
+
+        let imported = valid_agent_infos.len();
+        if imported > 0 {
+            admin_ws.add_agent_info(valid_agent_infos).await?;
+        }
+
+        log::info!("Imported {imported} agent infos.");
+
+        Ok(imported)
+    }
+}
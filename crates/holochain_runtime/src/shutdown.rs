@@ -0,0 +1,162 @@
+use std::time::Duration;
+
+use async_std::sync::Mutex;
+use serde::{Deserialize, Serialize};
+
+use crate::HolochainRuntime;
+
+/// How long the whole shutdown can take by default before giving up
+pub const DEFAULT_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The phases of `HolochainRuntime::shutdown()`, in the order they run
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShutdownPhase {
+    /// No new app websockets or zome call signatures are handed out, and mDNS discovery stops
+    StoppingZomeCalls,
+    /// The running apps are disabled, so that their cells leave the network and notify their peers
+    LeavingNetworks,
+    /// The conductor shuts down, closing its databases
+    FlushingDatabases,
+    /// The keystore and the local servers stop
+    StoppingKeystore,
+    /// Everything has stopped
+    Completed,
+}
+
+pub(crate) struct ShutdownState {
+    pub(crate) timeout: Duration,
+    /// `None` while the runtime is running
+    pub(crate) phase: Mutex<Option<ShutdownPhase>>,
+    /// Set to `true` when the shutdown starts
+    started: tokio::sync::watch::Sender<bool>,
+}
+
+impl ShutdownState {
+    pub(crate) fn new(timeout: Duration) -> Self {
+        Self {
+            timeout,
+            phase: Mutex::new(None),
+            started: tokio::sync::watch::Sender::new(false),
+        }
+    }
+}
+
+impl HolochainRuntime {
+    /// How long `Self::shutdown()` can take before giving up, set with `HolochainRuntimeConfig::shutdown_timeout()`
+    pub fn shutdown_timeout(&self) -> Duration {
+        self.shutdown_state.timeout
+    }
+
+    /// Fails with `Error::ShuttingDown` if `Self::shutdown()` has been called
+    pub(crate) async fn ensure_running(&self) -> crate::Result<()> {
+        match *self.shutdown_state.phase.lock().await {
+            None => Ok(()),
+            Some(_) => Err(crate::Error::ShuttingDown),
+        }
+    }
+
+    /// Resolves once `Self::shutdown()` has been called, to stop the tasks that use the runtime
+    pub async fn shutdown_started(&self) {
+        let mut started = self.shutdown_state.started.subscribe();
+        // The sender lives as long as the runtime, so this can't fail
+        let _ = started.wait_for(|started| *started).await;
+    }
+
+    /// Shuts down the runtime, going through all the `ShutdownPhase`s in order
+    /// Gives up with `Error::ShutdownTimeout` if it takes longer than `HolochainRuntimeConfig::shutdown_timeout`
+    ///
+    /// The apps running at this point are enabled again in the next launch,
+    /// even if the process is killed in the middle of the shutdown
+    pub async fn shutdown(&self) -> crate::Result<()> {
+        self.shutdown_with_progress(|_phase| {}).await
+    }
+
+    /// Same as `Self::shutdown()`, calling `on_progress` as each phase starts
+    /// Calling it again while a shutdown is in progress does nothing
+    ///
+    /// * `on_progress` - called with each `ShutdownPhase` as it starts
+    pub async fn shutdown_with_progress<F>(&self, on_progress: F) -> crate::Result<()>
+    where
+        F: Fn(ShutdownPhase) + Send + Sync,
+    {
+        {
+            let mut phase = self.shutdown_state.phase.lock().await;
+            if phase.is_some() {
+                return Ok(());
+            }
+            *phase = Some(ShutdownPhase::StoppingZomeCalls);
+        }
+        self.shutdown_state.started.send_replace(true);
+
+        let timeout = self.shutdown_state.timeout;
+        let shutdown = async {
+            // Waits for a relaunch of the conductor in progress to finish, and keeps new ones from starting,
+            // since they check that the runtime is running only once they hold this lock
+            let _relaunch_guard = self.conductor_launcher.network_profile.lock().await;
+            self.run_shutdown_phases(&on_progress).await
+        };
+        match tokio::time::timeout(timeout, shutdown).await {
+            Ok(result) => result,
+            Err(_) => {
+                let phase = *self.shutdown_state.phase.lock().await;
+                log::error!("Shutdown timed out after {timeout:?} in phase {phase:?}.");
+                Err(crate::Error::ShutdownTimeout(format!("{phase:?}")))
+            }
+        }
+    }
+
+    async fn enter_shutdown_phase<F>(&self, phase: ShutdownPhase, on_progress: &F)
+    where
+        F: Fn(ShutdownPhase) + Send + Sync,
+    {
+        *self.shutdown_state.phase.lock().await = Some(phase);
+        log::info!("Shutdown phase: {phase:?}.");
+        on_progress(phase);
+    }
+
+    async fn run_shutdown_phases<F>(&self, on_progress: &F) -> crate::Result<()>
+    where
+        F: Fn(ShutdownPhase) + Send + Sync,
+    {
+        self.enter_shutdown_phase(ShutdownPhase::StoppingZomeCalls, on_progress)
+            .await;
+        self.apps_websockets_auths.lock().await.clear();
+        self.mdns_discovery.shutdown()?;
+
+        // Leave all networks using `disable_app()`, which will make the cells leave the network
+        // and notify the bootstrap server and the peers about it
+        self.enter_shutdown_phase(ShutdownPhase::LeavingNetworks, on_progress)
+            .await;
+        self.disable_running_apps().await?;
+
+        self.enter_shutdown_phase(ShutdownPhase::FlushingDatabases, on_progress)
+            .await;
+        self.conductor_handle()
+            .await
+            .shutdown()
+            .await
+            .map_err(|e| crate::Error::HolochainShutdownError(e.to_string()))?
+            .map_err(|e| crate::Error::HolochainShutdownError(e.to_string()))?;
+
+        self.enter_shutdown_phase(ShutdownPhase::StoppingKeystore, on_progress)
+            .await;
+        self.conductor_launcher
+            .keystore()
+            .await
+            .lair_client()
+            .shutdown()
+            .await
+            .map_err(|err| crate::Error::LairError(err))?;
+
+        // Stop the local servers last, so that the apps could notify the peers about leaving through them
+        #[cfg(feature = "local-servers")]
+        if let Some(local_servers) = &self.local_servers {
+            local_servers.shutdown()?;
+        }
+
+        self.enter_shutdown_phase(ShutdownPhase::Completed, on_progress)
+            .await;
+
+        Ok(())
+    }
+}
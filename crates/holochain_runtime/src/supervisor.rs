@@ -0,0 +1,243 @@
+use std::{sync::Arc, time::Duration};
+
+use holochain_client::InstalledAppId;
+
+use crate::{AppWebsocketAuth, HolochainRuntime, NetworkProfile};
+
+/// Interval between health checks of the conductor and the keystore
+const HEALTH_CHECK_INTERVAL: Duration = Duration::from_secs(10);
+
+/// How long a health check can take before it's considered failed
+const HEALTH_CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Consecutive failed health checks after which the conductor is considered crashed
+const FAILED_HEALTH_CHECKS_BEFORE_RESTART: u32 = 3;
+
+/// Restarts attempted before giving up on the conductor
+const MAX_RESTART_ATTEMPTS: u32 = 3;
+
+/// What the supervisor spawned with `HolochainRuntime::spawn_supervisor()` reports
+#[derive(Debug, Clone)]
+pub enum SupervisorEvent {
+    /// The conductor or the keystore stopped responding, with the reason why
+    ConductorCrashed(String),
+    /// The conductor was restarted, after a crash or by `HolochainRuntime::set_network_profile()` or `HolochainRuntime::create_backup()`,
+    /// and the app websockets were attached again on the same ports with new tokens
+    ConductorRestarted(Vec<AppWebsocketAuth>),
+    /// All the restart attempts failed, so the supervisor has stopped
+    RestartFailed(String),
+}
+
+/// The `on_event` callback given to `HolochainRuntime::spawn_supervisor()`
+pub(crate) type SupervisorListener = Arc<dyn Fn(SupervisorEvent) + Send + Sync>;
+
+fn supervisor_error(reason: String) -> crate::Error {
+    crate::Error::SupervisorError(reason)
+}
+
+impl HolochainRuntime {
+    /// Spawns a task that checks the health of the conductor and the keystore periodically,
+    /// and restarts them in-process if they stop responding
+    /// The task stops when the runtime is shut down
+    ///
+    /// * `on_event` - called when the conductor crashes and when it is restarted, also when it's relaunched
+    ///   by `Self::set_network_profile()` or `Self::create_backup()`
+    pub fn spawn_supervisor<F>(&self, on_event: F)
+    where
+        F: Fn(SupervisorEvent) + Send + Sync + 'static,
+    {
+        let on_event: SupervisorListener = Arc::new(on_event);
+        match self.supervisor_listener.write() {
+            Ok(mut listener) => *listener = Some(on_event.clone()),
+            Err(err) => log::error!("Failed to register the supervisor listener: {err:?}."),
+        }
+
+        let runtime = self.clone();
+        tokio::spawn(async move {
+            let mut failed_health_checks = 0;
+            loop {
+                tokio::time::sleep(HEALTH_CHECK_INTERVAL).await;
+
+                if runtime.ensure_running().await.is_err() {
+                    return;
+                }
+                // The conductor is being relaunched with another network profile
+                let Some(relaunch_guard) = runtime.conductor_launcher.network_profile.try_lock()
+                else {
+                    continue;
+                };
+
+                let Err(err) = runtime.check_health().await else {
+                    failed_health_checks = 0;
+                    continue;
+                };
+                failed_health_checks += 1;
+                log::warn!("Failed health check {failed_health_checks}: {err:?}.");
+                if failed_health_checks < FAILED_HEALTH_CHECKS_BEFORE_RESTART {
+                    continue;
+                }
+                failed_health_checks = 0;
+
+                log::error!("The conductor crashed: {err:?}.");
+                on_event(SupervisorEvent::ConductorCrashed(err.to_string()));
+                drop(relaunch_guard);
+
+                match runtime.restart_with_retries().await {
+                    Ok(app_websockets_auths) => {
+                        on_event(SupervisorEvent::ConductorRestarted(app_websockets_auths))
+                    }
+                    Err(err) => {
+                        log::error!("Giving up on restarting the conductor: {err:?}.");
+                        on_event(SupervisorEvent::RestartFailed(err.to_string()));
+                        return;
+                    }
+                }
+            }
+        });
+    }
+
+    /// Calls the `on_event` callback given to `Self::spawn_supervisor()`, if it was spawned
+    pub(crate) fn notify_supervisor_listener(&self, event: SupervisorEvent) {
+        let listener = match self.supervisor_listener.read() {
+            Ok(listener) => listener.clone(),
+            Err(err) => {
+                log::error!("Failed to read the supervisor listener: {err:?}.");
+                return;
+            }
+        };
+        if let Some(listener) = listener {
+            listener(event);
+        }
+    }
+
+    /// Checks that the keystore and the conductor respond in time
+    async fn check_health(&self) -> crate::Result<()> {
+        self.check_keystore_health().await?;
+
+        tokio::time::timeout(HEALTH_CHECK_TIMEOUT, async {
+            let admin_ws = self.admin_websocket().await?;
+            admin_ws.list_apps(None).await?;
+            crate::Result::Ok(())
+        })
+        .await
+        .map_err(|_| supervisor_error(String::from("The conductor is not responding")))?
+    }
+
+    async fn check_keystore_health(&self) -> crate::Result<()> {
+        let keystore = self.conductor_launcher.keystore().await;
+        tokio::time::timeout(HEALTH_CHECK_TIMEOUT, keystore.lair_client().list_entries())
+            .await
+            .map_err(|_| supervisor_error(String::from("The keystore is not responding")))?
+            .map_err(|err| crate::Error::LairError(err))?;
+        Ok(())
+    }
+
+    async fn restart_with_retries(&self) -> crate::Result<Vec<AppWebsocketAuth>> {
+        let mut attempt = 1;
+        loop {
+            match self.restart_conductor().await {
+                Ok(app_websockets_auths) => return Ok(app_websockets_auths),
+                Err(err) if attempt < MAX_RESTART_ATTEMPTS => {
+                    log::error!("Restart attempt {attempt} failed: {err:?}.");
+                    tokio::time::sleep(HEALTH_CHECK_INTERVAL * attempt).await;
+                    attempt += 1;
+                }
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    /// Restarts the conductor in-process with the same config, respawning the keystore first if it's not responding
+    /// Attaches the app websockets again on the same ports they had, and issues new tokens for them
+    async fn restart_conductor(&self) -> crate::Result<Vec<AppWebsocketAuth>> {
+        let network_profile = self.conductor_launcher.network_profile.lock().await;
+        self.ensure_running().await?;
+
+        log::info!("Restarting the conductor.");
+
+        self.mdns_discovery.shutdown()?;
+
+        if self.check_keystore_health().await.is_err() {
+            self.conductor_launcher.respawn_keystore().await?;
+        }
+
+        // The old conductor may be partially alive, holding the admin port and the databases
+        if let Err(err) = self.conductor_handle().await.shutdown().await {
+            log::warn!("Error shutting down the crashed conductor: {err:?}.");
+        }
+
+        let app_websockets_auths = self.start_conductor(&network_profile).await?;
+
+        log::info!("Restarted the conductor.");
+
+        Ok(app_websockets_auths)
+    }
+
+    /// Builds and starts a new conductor with the given network profile, replacing the one that was running,
+    /// which must have been shut down already
+    /// Enables the apps disabled by the runtime and attaches the app websockets again on the same ports they had,
+    /// returning their new tokens
+    pub(crate) async fn start_conductor(
+        &self,
+        network_profile: &NetworkProfile,
+    ) -> crate::Result<Vec<AppWebsocketAuth>> {
+        let conductor_handle = self
+            .conductor_launcher
+            .build_conductor(network_profile)
+            .await?;
+        *self.conductor_handle.write().await = conductor_handle;
+
+        // Apps disabled by the runtime before the conductor was shut down, or when it crashed, e.g. in the middle of a network profile switch
+        let apps_disabled_by_runtime: Vec<InstalledAppId> = self
+            .filesystem
+            .apps_disabled_by_runtime_store
+            .get()?
+            .into_iter()
+            .collect();
+        self.enable_apps(apps_disabled_by_runtime).await;
+
+        let app_websockets_auths = self.restore_app_websockets().await?;
+
+        self.mdns_discovery
+            .set_network_profile(network_profile.clone())
+            .await?;
+
+        Ok(app_websockets_auths)
+    }
+
+    async fn restore_app_websockets(&self) -> crate::Result<Vec<AppWebsocketAuth>> {
+        let admin_ws = self.admin_websocket().await?;
+        let mut apps_websockets_auths = self.apps_websockets_auths.lock().await;
+
+        let mut restored_auths: Vec<AppWebsocketAuth> = Vec::new();
+        for auth in apps_websockets_auths.iter() {
+            let app_websocket_port = admin_ws
+                .attach_app_interface(
+                    auth.app_websocket_port,
+                    auth.allowed_origins.clone(),
+                    Some(auth.app_id.clone()),
+                )
+                .await?;
+            let response = admin_ws
+                .issue_app_auth_token(
+                    holochain_conductor_api::IssueAppAuthenticationTokenPayload {
+                        installed_app_id: auth.app_id.clone(),
+                        expiry_seconds: 999999999,
+                        single_use: false,
+                    },
+                )
+                .await?;
+
+            restored_auths.push(AppWebsocketAuth {
+                app_id: auth.app_id.clone(),
+                app_websocket_port,
+                allowed_origins: auth.allowed_origins.clone(),
+                token: response.token,
+            });
+        }
+
+        *apps_websockets_auths = restored_auths.clone();
+
+        Ok(restored_auths)
+    }
+}
@@ -0,0 +1,275 @@
+//! Helpers to test apps on multiple `HolochainRuntime`s running in the same process,
+//! connected to each other through in-process bootstrap and signal servers, so that no network access is needed
+
+use std::{
+    net::{Ipv4Addr, SocketAddr},
+    path::PathBuf,
+    time::Duration,
+};
+
+use holochain::prelude::{ExternIO, FunctionName, ZomeName};
+use holochain_client::{AppInfo, InstalledAppId, ZomeCallTarget};
+use holochain_conductor_api::{CellInfo, FullStateDump};
+use holochain_types::{
+    app::AppBundle,
+    prelude::{CellId, RoleName},
+    websocket::AllowedOrigins,
+};
+use serde::{de::DeserializeOwned, Serialize};
+use tempfile::TempDir;
+
+use crate::{
+    vec_to_locked, HolochainRuntime, HolochainRuntimeConfig, LocalServers, LocalServersMode,
+    NetworkConfig,
+};
+
+/// Interval between checks while waiting for the runtimes to reach some state
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+fn testing_error(reason: String) -> crate::Error {
+    crate::Error::TestingError(reason)
+}
+
+/// A set of runtimes, each of them with its own temporary directory,
+/// connected to the same in-process bootstrap and signal servers
+pub struct TestNetwork {
+    pub runtimes: Vec<HolochainRuntime>,
+    pub local_servers: LocalServers,
+    dirs: Vec<TempDir>,
+}
+
+impl TestNetwork {
+    /// Launches `runtimes_count` runtimes in temporary directories
+    pub async fn launch(runtimes_count: usize) -> crate::Result<Self> {
+        let local_servers = LocalServers::spawn(
+            SocketAddr::new(Ipv4Addr::LOCALHOST.into(), 0),
+            LocalServersMode::Testing,
+        )
+        .await?;
+
+        let mut network = Self {
+            runtimes: Vec::new(),
+            local_servers,
+            dirs: Vec::new(),
+        };
+        for _ in 0..runtimes_count {
+            let dir = tempfile::tempdir()?;
+            let runtime = network.launch_runtime_in(dir.path().to_path_buf()).await?;
+            network.runtimes.push(runtime);
+            network.dirs.push(dir);
+        }
+
+        Ok(network)
+    }
+
+    /// The config for a runtime in the given directory, connected to the local servers of this network
+    pub fn runtime_config(&self, holochain_dir: PathBuf) -> HolochainRuntimeConfig {
+        let mut network_config = NetworkConfig::default();
+        network_config.bootstrap_url = self.local_servers.bootstrap_url();
+        network_config.signal_url = self.local_servers.signal_url();
+
+        HolochainRuntimeConfig::new(holochain_dir, network_config)
+    }
+
+    async fn launch_runtime_in(&self, holochain_dir: PathBuf) -> crate::Result<HolochainRuntime> {
+        HolochainRuntime::launch(vec_to_locked(vec![]), self.runtime_config(holochain_dir)).await
+    }
+
+    /// The directory where the runtime with the given index stores its data
+    pub fn holochain_dir(&self, runtime_index: usize) -> PathBuf {
+        self.dirs[runtime_index].path().to_path_buf()
+    }
+
+    /// Shuts down the runtime with the given index, and launches it again from the same directory
+    pub async fn restart(&mut self, runtime_index: usize) -> crate::Result<()> {
+        self.runtimes[runtime_index].shutdown().await?;
+
+        let runtime = self
+            .launch_runtime_in(self.holochain_dir(runtime_index))
+            .await?;
+        self.runtimes[runtime_index] = runtime;
+
+        Ok(())
+    }
+
+    /// Installs the given app in every runtime
+    pub async fn install_app(
+        &self,
+        app_id: InstalledAppId,
+        app_bundle: AppBundle,
+    ) -> crate::Result<Vec<AppInfo>> {
+        let mut app_infos = Vec::new();
+        for runtime in &self.runtimes {
+            let app_info = runtime
+                .install_app(app_id.clone(), app_bundle.clone(), None, None, None)
+                .await?;
+            app_infos.push(app_info);
+        }
+        Ok(app_infos)
+    }
+
+    /// Waits until every cell of the given app in every runtime sees the agents of all the other runtimes as peers
+    pub async fn wait_for_peers(
+        &self,
+        app_id: &InstalledAppId,
+        timeout: Duration,
+    ) -> crate::Result<()> {
+        let expected_peers = self.runtimes.len() - 1;
+
+        self.wait_until(timeout, "peers to see each other", || async {
+            for runtime in &self.runtimes {
+                let network_status = runtime.network_status().await?;
+                let Some(app) = network_status
+                    .apps
+                    .into_iter()
+                    .find(|app| app.installed_app_id.eq(app_id))
+                else {
+                    return Ok(false);
+                };
+                let all_cells_see_peers = app
+                    .roles
+                    .values()
+                    .flatten()
+                    .all(|cell| cell.peers.len() >= expected_peers);
+                if !all_cells_see_peers {
+                    return Ok(false);
+                }
+            }
+            Ok(true)
+        })
+        .await
+    }
+
+    /// Waits until all the cells of the given app have integrated the same DHT operations,
+    /// and none of them has any operation left to validate or integrate
+    pub async fn await_consistency(
+        &self,
+        app_id: &InstalledAppId,
+        timeout: Duration,
+    ) -> crate::Result<()> {
+        self.wait_until(timeout, "consistency", || async {
+            let mut integrated_ops_by_role: Vec<(RoleName, usize)> = Vec::new();
+
+            for runtime in &self.runtimes {
+                for (role_name, cell_id) in app_cells(runtime, app_id).await? {
+                    let state = full_state(runtime, cell_id).await?;
+                    let integration = state.integration_dump;
+                    if !integration.validation_limbo.is_empty()
+                        || !integration.integration_limbo.is_empty()
+                    {
+                        return Ok(false);
+                    }
+                    integrated_ops_by_role.push((role_name, integration.integrated.len()));
+                }
+            }
+
+            let consistent = integrated_ops_by_role.iter().all(|(role_name, count)| {
+                integrated_ops_by_role
+                    .iter()
+                    .filter(|(other_role_name, _)| other_role_name.eq(role_name))
+                    .all(|(_, other_count)| other_count == count)
+            });
+            Ok(consistent)
+        })
+        .await
+    }
+
+    /// Calls a zome function of the given app in the runtime with the given index
+    pub async fn call_zome<I, O>(
+        &self,
+        runtime_index: usize,
+        app_id: InstalledAppId,
+        role_name: RoleName,
+        zome_name: &str,
+        fn_name: &str,
+        payload: I,
+    ) -> crate::Result<O>
+    where
+        I: Serialize + std::fmt::Debug,
+        O: DeserializeOwned + std::fmt::Debug,
+    {
+        let app_ws = self.runtimes[runtime_index]
+            .app_websocket(app_id, AllowedOrigins::Any)
+            .await?;
+
+        let result = app_ws
+            .call_zome(
+                ZomeCallTarget::RoleName(role_name),
+                ZomeName::from(zome_name),
+                FunctionName::from(fn_name),
+                ExternIO::encode(payload)?,
+            )
+            .await?;
+
+        Ok(result.decode()?)
+    }
+
+    async fn wait_until<F, Fut>(
+        &self,
+        timeout: Duration,
+        description: &str,
+        condition: F,
+    ) -> crate::Result<()>
+    where
+        F: Fn() -> Fut,
+        Fut: std::future::Future<Output = crate::Result<bool>>,
+    {
+        let result = tokio::time::timeout(timeout, async {
+            loop {
+                if condition().await? {
+                    return Ok(());
+                }
+                tokio::time::sleep(POLL_INTERVAL).await;
+            }
+        })
+        .await;
+
+        match result {
+            Ok(result) => result,
+            Err(_) => Err(testing_error(format!(
+                "Timed out after {timeout:?} waiting for {description}"
+            ))),
+        }
+    }
+
+    /// Shuts down all the runtimes and the local servers
+    pub async fn shutdown(self) -> crate::Result<()> {
+        for runtime in &self.runtimes {
+            runtime.shutdown().await?;
+        }
+        self.local_servers.shutdown()?;
+        Ok(())
+    }
+}
+
+async fn app_cells(
+    runtime: &HolochainRuntime,
+    app_id: &InstalledAppId,
+) -> crate::Result<Vec<(RoleName, CellId)>> {
+    let admin_ws = runtime.admin_websocket().await?;
+    let app = admin_ws
+        .list_apps(None)
+        .await?
+        .into_iter()
+        .find(|app| app.installed_app_id.eq(app_id))
+        .ok_or(crate::Error::AppDoesNotExist(app_id.clone()))?;
+
+    let cells = app
+        .cell_info
+        .into_iter()
+        .flat_map(|(role_name, cells)| {
+            cells.into_iter().filter_map(move |cell| match cell {
+                CellInfo::Provisioned(c) => Some((role_name.clone(), c.cell_id)),
+                CellInfo::Cloned(c) => Some((role_name.clone(), c.cell_id)),
+                CellInfo::Stem(_) => None,
+            })
+        })
+        .collect();
+    Ok(cells)
+}
+
+async fn full_state(runtime: &HolochainRuntime, cell_id: CellId) -> crate::Result<FullStateDump> {
+    let admin_ws = runtime.admin_websocket().await?;
+    let state = admin_ws.dump_full_state(cell_id, None).await?;
+    Ok(state)
+}
@@ -0,0 +1,159 @@
+use std::{path::PathBuf, process::Command, sync::OnceLock, time::Duration};
+
+use holochain::prelude::{Link, Record};
+use holochain_runtime::{testing::TestNetwork, UpdateHappError};
+use holochain_types::app::AppBundle;
+use serde::{Deserialize, Serialize};
+
+const APP_ID: &str = "forum";
+const ROLE_NAME: &str = "forum";
+const TIMEOUT: Duration = Duration::from_secs(60);
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct Post {
+    title: String,
+    content: String,
+}
+
+/// Builds the example forum hApp in `examples/end-user-happ` with the same commands as its `build:happ` script,
+/// which need the `wasm32-unknown-unknown` target and the `hc` CLI of the dev shell
+fn build_forum_happ() -> PathBuf {
+    let happ_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../../examples/end-user-happ");
+
+    run(Command::new("cargo")
+        .current_dir(&happ_dir)
+        .env("CARGO_TARGET_DIR", "target")
+        .args([
+            "build",
+            "--release",
+            "--target",
+            "wasm32-unknown-unknown",
+            "--workspace",
+            "--exclude",
+            "example-end-user-happ",
+        ]));
+    run(Command::new("hc")
+        .current_dir(&happ_dir)
+        .args(["app", "pack", "workdir", "--recursive"]));
+
+    happ_dir.join("workdir").join("forum.happ")
+}
+
+fn run(command: &mut Command) {
+    let status = command
+        .status()
+        .unwrap_or_else(|err| panic!("Failed to run {command:?}: {err:?}"));
+    assert!(status.success(), "{command:?} failed with {status}");
+}
+
+/// The example forum hApp, built the first time it's needed,
+/// or the hApp at the path in the `HOLOCHAIN_RUNTIME_TEST_HAPP` environment variable
+fn forum_happ() -> AppBundle {
+    static HAPP_PATH: OnceLock<PathBuf> = OnceLock::new();
+    let path = HAPP_PATH.get_or_init(|| match std::env::var("HOLOCHAIN_RUNTIME_TEST_HAPP") {
+        Ok(path) => PathBuf::from(path),
+        Err(_) => build_forum_happ(),
+    });
+    let bytes = std::fs::read(path)
+        .unwrap_or_else(|err| panic!("Failed to read the test hApp at {path:?}: {err:?}"));
+    AppBundle::decode(&bytes).expect("Failed to decode the test hApp")
+}
+
+async fn create_post(network: &TestNetwork, runtime_index: usize, title: &str) -> Record {
+    network
+        .call_zome(
+            runtime_index,
+            APP_ID.into(),
+            ROLE_NAME.into(),
+            "posts",
+            "create_post",
+            Post {
+                title: title.into(),
+                content: String::from("Hello"),
+            },
+        )
+        .await
+        .unwrap()
+}
+
+async fn get_all_posts(network: &TestNetwork, runtime_index: usize) -> Vec<Link> {
+    network
+        .call_zome(
+            runtime_index,
+            APP_ID.into(),
+            ROLE_NAME.into(),
+            "posts",
+            "get_all_posts",
+            (),
+        )
+        .await
+        .unwrap()
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn peers_see_each_others_posts() {
+    let network = TestNetwork::launch(2).await.unwrap();
+    network
+        .install_app(APP_ID.into(), forum_happ())
+        .await
+        .unwrap();
+    network
+        .wait_for_peers(&APP_ID.into(), TIMEOUT)
+        .await
+        .unwrap();
+
+    create_post(&network, 0, "First post").await;
+    network
+        .await_consistency(&APP_ID.into(), TIMEOUT)
+        .await
+        .unwrap();
+
+    assert_eq!(get_all_posts(&network, 1).await.len(), 1);
+
+    network.shutdown().await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn update_app_if_necessary_skips_same_bundle() {
+    let network = TestNetwork::launch(1).await.unwrap();
+    network
+        .install_app(APP_ID.into(), forum_happ())
+        .await
+        .unwrap();
+
+    let runtime = &network.runtimes[0];
+    runtime
+        .update_app_if_necessary(APP_ID.into(), forum_happ())
+        .await
+        .unwrap();
+    create_post(&network, 0, "After update").await;
+
+    let result = runtime
+        .update_app_if_necessary(String::from("unknown-app"), forum_happ())
+        .await;
+    assert!(matches!(
+        result,
+        Err(holochain_runtime::Error::UpdateAppError(
+            UpdateHappError::AppNotFound(_)
+        ))
+    ));
+
+    network.shutdown().await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn restart_reenables_apps() {
+    let mut network = TestNetwork::launch(1).await.unwrap();
+    network
+        .install_app(APP_ID.into(), forum_happ())
+        .await
+        .unwrap();
+    create_post(&network, 0, "Before restart").await;
+
+    network.restart(0).await.unwrap();
+
+    // The app was disabled on shutdown, so zome calls only succeed if it was enabled again on launch
+    assert_eq!(get_all_posts(&network, 0).await.len(), 1);
+
+    network.shutdown().await.unwrap();
+}
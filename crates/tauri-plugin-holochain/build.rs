@@ -5,6 +5,25 @@ const COMMANDS: &[&str] = &[
     "open_app",
     "list_apps",
     "is_holochain_ready",
+    "unlock",
+    "verify_passphrase",
+    "change_passphrase",
+    "export_device_seed_backup",
+    "unlock_and_restore_device_seed_backup",
+    "create_backup",
+    "restore_backup",
+    "generate_agent_key",
+    "list_agent_keys",
+    "set_agent_key_label",
+    "get_mdns_discovery_settings",
+    "set_mdns_discovery_enabled",
+    "set_mdns_discovery_participants",
+    "network_status",
+    "get_network_profile",
+    "set_network_profile",
+    "export_peer_info",
+    "import_peer_info",
+    "get_ui_asset_manifest",
 ];
 
 fn main() {
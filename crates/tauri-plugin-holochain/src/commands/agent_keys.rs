@@ -0,0 +1,47 @@
+use holochain_client::AgentPubKey;
+use holochain_runtime::AgentKey;
+use tauri::{command, AppHandle, Runtime, WebviewWindow};
+
+use crate::HolochainExt;
+
+#[command]
+pub(crate) async fn generate_agent_key<R: Runtime>(
+    app_handle: AppHandle<R>,
+    window: WebviewWindow<R>,
+    label: Option<String>,
+) -> crate::Result<AgentPubKey> {
+    let agent_pub_key = app_handle
+        .window_holochain(window.label())?
+        .holochain_runtime
+        .generate_agent_key(label)
+        .await?;
+    Ok(agent_pub_key)
+}
+
+#[command]
+pub(crate) async fn list_agent_keys<R: Runtime>(
+    app_handle: AppHandle<R>,
+    window: WebviewWindow<R>,
+) -> crate::Result<Vec<AgentKey>> {
+    let agent_keys = app_handle
+        .window_holochain(window.label())?
+        .holochain_runtime
+        .list_agent_keys()
+        .await?;
+    Ok(agent_keys)
+}
+
+#[command]
+pub(crate) async fn set_agent_key_label<R: Runtime>(
+    app_handle: AppHandle<R>,
+    window: WebviewWindow<R>,
+    agent_pub_key: AgentPubKey,
+    label: Option<String>,
+) -> crate::Result<()> {
+    app_handle
+        .window_holochain(window.label())?
+        .holochain_runtime
+        .set_agent_key_label(agent_pub_key, label)
+        .await?;
+    Ok(())
+}
@@ -0,0 +1,47 @@
+use std::path::PathBuf;
+
+use holochain_runtime::{BackupManifest, HolochainRuntime};
+use tauri::{command, AppHandle, Manager, Runtime, WebviewWindow};
+
+use crate::{HolochainExt, HolochainPlugin, LockedHolochain};
+
+#[command]
+pub(crate) async fn create_backup<R: Runtime>(
+    app_handle: AppHandle<R>,
+    window: WebviewWindow<R>,
+    backup_path: PathBuf,
+) -> crate::Result<()> {
+    app_handle
+        .window_holochain(window.label())?
+        .holochain_runtime
+        .create_backup(backup_path)
+        .await?;
+    Ok(())
+}
+
+/// Only available while holochain is locked, since the backup must be restored before launching it
+#[command]
+pub(crate) async fn restore_backup<R: Runtime>(
+    app_handle: AppHandle<R>,
+    backup_path: PathBuf,
+) -> crate::Result<BackupManifest> {
+    let locked_holochain = app_handle
+        .try_state::<LockedHolochain>()
+        .ok_or(crate::Error::HolochainNotLockedError)?;
+    let _unlocking = locked_holochain.unlocking.lock().await;
+
+    if app_handle.try_state::<HolochainPlugin<R>>().is_some() {
+        return Err(crate::Error::HolochainNotLockedError);
+    }
+
+    let manifest = HolochainRuntime::restore_backup(
+        backup_path,
+        locked_holochain
+            .config
+            .holochain_runtime_config
+            .holochain_dir
+            .clone(),
+    )
+    .await?;
+    Ok(manifest)
+}
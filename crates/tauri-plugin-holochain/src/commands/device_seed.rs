@@ -0,0 +1,34 @@
+use holochain_runtime::vec_to_locked;
+use tauri::{command, AppHandle, Runtime, WebviewWindow};
+
+use crate::HolochainExt;
+
+#[command]
+pub(crate) async fn export_device_seed_backup<R: Runtime>(
+    app_handle: AppHandle<R>,
+    window: WebviewWindow<R>,
+    passphrase: String,
+) -> crate::Result<Vec<u8>> {
+    let backup = app_handle
+        .window_holochain(window.label())?
+        .holochain_runtime
+        .export_device_seed_backup(vec_to_locked(passphrase.into_bytes()))
+        .await?;
+    Ok(backup)
+}
+
+#[command]
+pub(crate) async fn unlock_and_restore_device_seed_backup<R: Runtime>(
+    app_handle: AppHandle<R>,
+    passphrase: String,
+    device_seed_backup: Vec<u8>,
+    device_seed_backup_passphrase: String,
+) -> crate::Result<()> {
+    crate::unlock_and_restore_device_seed_backup(
+        app_handle,
+        vec_to_locked(passphrase.into_bytes()),
+        device_seed_backup,
+        vec_to_locked(device_seed_backup_passphrase.into_bytes()),
+    )
+    .await
+}
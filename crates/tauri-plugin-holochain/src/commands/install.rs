@@ -1,17 +1,19 @@
 use crate::HolochainExt;
-use holochain_client::AppInfo;
+use holochain_client::{AgentPubKey, AppInfo};
 use holochain_types::{app::RoleSettingsMap, web_app::WebAppBundle};
-use tauri::{command, AppHandle, Runtime};
+use tauri::{command, AppHandle, Runtime, WebviewWindow};
 
 #[command]
 pub(crate) async fn install_web_app<R: Runtime>(
     app: AppHandle<R>,
+    window: WebviewWindow<R>,
     app_id: String,
     web_app_bundle: WebAppBundle,
     roles_settings: Option<RoleSettingsMap>,
+    agent: Option<AgentPubKey>,
     network_seed: Option<String>
 ) -> crate::Result<AppInfo> {
-    app.holochain()?.install_web_app(app_id, web_app_bundle, roles_settings, None, network_seed)
+    app.window_holochain(window.label())?.install_web_app(app_id, web_app_bundle, roles_settings, agent, network_seed)
         .await
 }
 
@@ -19,18 +21,19 @@ pub(crate) async fn install_web_app<R: Runtime>(
 #[command]
 pub(crate) async fn uninstall_web_app<R: Runtime>(
     app: AppHandle<R>,
+    window: WebviewWindow<R>,
     app_id: String,
 ) -> crate::Result<()> {
-    let admin_ws = app.holochain()?.admin_websocket().await?;
-     admin_ws.uninstall_app(app_id, false).await.map_err(|err| crate::Error::ConductorApiError(err))?;
+    app.window_holochain(window.label())?.uninstall_app(app_id).await?;
     Ok(())
 }
 
 #[command]
 pub(crate) async fn list_apps<R: Runtime>(
     app: AppHandle<R>,
+    window: WebviewWindow<R>,
 ) -> crate::Result<Vec<AppInfo>> {
-    let admin_ws = app.holochain()?.admin_websocket().await?;
+    let admin_ws = app.window_holochain(window.label())?.admin_websocket().await?;
     let apps = admin_ws.list_apps(None).await.map_err(|err| crate::Error::ConductorApiError(err))?;
 
     Ok(apps)
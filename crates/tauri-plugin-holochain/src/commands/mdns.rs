@@ -0,0 +1,44 @@
+use holochain_runtime::{MdnsDiscoverySettings, MdnsParticipants};
+use tauri::{command, AppHandle, Runtime, WebviewWindow};
+
+use crate::HolochainExt;
+
+#[command]
+pub(crate) async fn get_mdns_discovery_settings<R: Runtime>(
+    app_handle: AppHandle<R>,
+    window: WebviewWindow<R>,
+) -> crate::Result<MdnsDiscoverySettings> {
+    let settings = app_handle
+        .window_holochain(window.label())?
+        .holochain_runtime
+        .mdns_discovery_settings()?;
+    Ok(settings)
+}
+
+#[command]
+pub(crate) async fn set_mdns_discovery_enabled<R: Runtime>(
+    app_handle: AppHandle<R>,
+    window: WebviewWindow<R>,
+    enabled: bool,
+) -> crate::Result<()> {
+    app_handle
+        .window_holochain(window.label())?
+        .holochain_runtime
+        .set_mdns_discovery_enabled(enabled)
+        .await?;
+    Ok(())
+}
+
+#[command]
+pub(crate) async fn set_mdns_discovery_participants<R: Runtime>(
+    app_handle: AppHandle<R>,
+    window: WebviewWindow<R>,
+    participants: MdnsParticipants,
+) -> crate::Result<()> {
+    app_handle
+        .window_holochain(window.label())?
+        .holochain_runtime
+        .set_mdns_discovery_participants(participants)
+        .await?;
+    Ok(())
+}
@@ -1,4 +1,13 @@
+pub mod agent_keys;
+pub mod backup;
+pub mod device_seed;
 pub mod get_runtime_info;
+pub mod mdns;
+pub mod network_profile;
+pub mod network_status;
 pub mod open_app;
+pub mod passphrase;
+pub mod peer_info;
 pub mod sign_zome_call;
+pub mod ui;
 pub mod install;
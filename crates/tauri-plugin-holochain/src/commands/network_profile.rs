@@ -0,0 +1,32 @@
+use holochain_runtime::NetworkProfile;
+use tauri::{command, AppHandle, Emitter, Runtime, WebviewWindow};
+
+use crate::HolochainExt;
+
+#[command]
+pub(crate) async fn get_network_profile<R: Runtime>(
+    app_handle: AppHandle<R>,
+    window: WebviewWindow<R>,
+) -> crate::Result<NetworkProfile> {
+    let network_profile = app_handle
+        .window_holochain(window.label())?
+        .holochain_runtime
+        .network_profile()
+        .await;
+    Ok(network_profile)
+}
+
+#[command]
+pub(crate) async fn set_network_profile<R: Runtime>(
+    app_handle: AppHandle<R>,
+    window: WebviewWindow<R>,
+    network_profile: NetworkProfile,
+) -> crate::Result<()> {
+    app_handle
+        .window_holochain(window.label())?
+        .holochain_runtime
+        .set_network_profile(network_profile.clone())
+        .await?;
+    app_handle.emit("holochain://network-profile-changed", network_profile)?;
+    Ok(())
+}
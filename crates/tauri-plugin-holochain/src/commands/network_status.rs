@@ -0,0 +1,17 @@
+use holochain_runtime::NetworkStatus;
+use tauri::{command, AppHandle, Runtime, WebviewWindow};
+
+use crate::HolochainExt;
+
+#[command]
+pub(crate) async fn network_status<R: Runtime>(
+    app_handle: AppHandle<R>,
+    window: WebviewWindow<R>,
+) -> crate::Result<NetworkStatus> {
+    let network_status = app_handle
+        .window_holochain(window.label())?
+        .holochain_runtime
+        .network_status()
+        .await?;
+    Ok(network_status)
+}
@@ -1,16 +1,17 @@
 use crate::HolochainExt;
-use tauri::{command, AppHandle, Runtime};
+use tauri::{command, AppHandle, Runtime, WebviewWindow};
 
 #[command]
 pub(crate) async fn open_app<R: Runtime>(
     app: AppHandle<R>,
+    window: WebviewWindow<R>,
     app_id: String,
     title: String,
     url_path: Option<String>,
 ) -> crate::Result<()> {
     #[cfg(mobile)]
     {
-        app.holochain()?
+        app.window_holochain(window.label())?
             .web_happ_window_builder(app_id, url_path)
             .await?
             .build()?;
@@ -18,7 +19,7 @@ pub(crate) async fn open_app<R: Runtime>(
 
     #[cfg(desktop)]
     {
-        app.holochain()?
+        app.window_holochain(window.label())?
             .web_happ_window_builder(app_id, url_path)
             .await?
             .title(title)
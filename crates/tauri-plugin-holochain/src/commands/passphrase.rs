@@ -0,0 +1,53 @@
+use holochain_runtime::{vec_to_locked, HolochainRuntime};
+use tauri::{command, AppHandle, Manager, Runtime, WebviewWindow};
+
+use crate::{HolochainExt, LockedHolochain};
+
+#[command]
+pub(crate) async fn unlock<R: Runtime>(
+    app_handle: AppHandle<R>,
+    passphrase: String,
+) -> crate::Result<()> {
+    crate::unlock(app_handle, vec_to_locked(passphrase.into_bytes())).await
+}
+
+#[command]
+pub(crate) async fn verify_passphrase<R: Runtime>(
+    app_handle: AppHandle<R>,
+    window: WebviewWindow<R>,
+    passphrase: String,
+) -> crate::Result<bool> {
+    let holochain_dir = match app_handle.window_holochain(window.label()) {
+        Ok(holochain) => holochain.holochain_runtime.filesystem.app_data_dir.clone(),
+        Err(_) => app_handle
+            .try_state::<LockedHolochain>()
+            .ok_or(crate::Error::HolochainNotInitializedError)?
+            .config
+            .holochain_runtime_config
+            .holochain_dir
+            .clone(),
+    };
+
+    let valid =
+        HolochainRuntime::verify_passphrase(holochain_dir, vec_to_locked(passphrase.into_bytes()))
+            .await?;
+    Ok(valid)
+}
+
+#[command]
+pub(crate) async fn change_passphrase<R: Runtime>(
+    app_handle: AppHandle<R>,
+    window: WebviewWindow<R>,
+    old_passphrase: String,
+    new_passphrase: String,
+) -> crate::Result<()> {
+    app_handle
+        .window_holochain(window.label())?
+        .holochain_runtime
+        .change_passphrase(
+            vec_to_locked(old_passphrase.into_bytes()),
+            vec_to_locked(new_passphrase.into_bytes()),
+        )
+        .await?;
+    Ok(())
+}
@@ -0,0 +1,32 @@
+use holochain_client::InstalledAppId;
+use tauri::{command, AppHandle, Runtime, WebviewWindow};
+
+use crate::HolochainExt;
+
+#[command]
+pub(crate) async fn export_peer_info<R: Runtime>(
+    app_handle: AppHandle<R>,
+    window: WebviewWindow<R>,
+    app_id: InstalledAppId,
+) -> crate::Result<String> {
+    let peer_info = app_handle
+        .window_holochain(window.label())?
+        .holochain_runtime
+        .export_peer_info(app_id)
+        .await?;
+    Ok(peer_info)
+}
+
+#[command]
+pub(crate) async fn import_peer_info<R: Runtime>(
+    app_handle: AppHandle<R>,
+    window: WebviewWindow<R>,
+    peer_info: String,
+) -> crate::Result<usize> {
+    let imported = app_handle
+        .window_holochain(window.label())?
+        .holochain_runtime
+        .import_peer_info(peer_info)
+        .await?;
+    Ok(imported)
+}
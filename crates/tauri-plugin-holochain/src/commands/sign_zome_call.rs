@@ -1,15 +1,16 @@
 use holochain_runtime::ZomeCallParamsSigned;
 use holochain_types::prelude::ZomeCallParams;
-use tauri::{command, AppHandle, Runtime};
+use tauri::{command, AppHandle, Runtime, WebviewWindow};
 
 use crate::HolochainExt;
 
 #[command]
 pub(crate) async fn sign_zome_call<R: Runtime>(
     app_handle: AppHandle<R>,
+    window: WebviewWindow<R>,
     zome_call_unsigned: ZomeCallParams,
 ) -> crate::Result<ZomeCallParamsSigned> {
-    let signed_zome_call = app_handle.holochain()?.holochain_runtime.sign_zome_call(zome_call_unsigned).await?;
+    let signed_zome_call = app_handle.window_holochain(window.label())?.holochain_runtime.sign_zome_call(zome_call_unsigned).await?;
 
     Ok(signed_zome_call)
 }
@@ -0,0 +1,29 @@
+use tauri::{command, AppHandle, Runtime, WebviewWindow};
+
+use crate::{
+    holochain_window,
+    http_server::{ui_asset_manifest, UiAssetManifest},
+    HolochainExt,
+};
+
+/// The asset manifest for the UI of the app open in the calling window,
+/// which must have been built with `HolochainPlugin::web_happ_window_builder()`
+#[command]
+pub(crate) async fn get_ui_asset_manifest<R: Runtime>(
+    app_handle: AppHandle<R>,
+    window: WebviewWindow<R>,
+) -> crate::Result<UiAssetManifest> {
+    let app_id = holochain_window(&app_handle, window.label())
+        .and_then(|window| window.app_id)
+        .ok_or(crate::Error::NotAHappWindow(window.label().to_string()))?;
+    let filesystem = app_handle
+        .window_holochain(window.label())?
+        .holochain_runtime
+        .filesystem
+        .clone();
+
+    let manifest =
+        tauri::async_runtime::spawn_blocking(move || ui_asset_manifest(&filesystem, &app_id))
+            .await??;
+    Ok(manifest)
+}
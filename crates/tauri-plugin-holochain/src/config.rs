@@ -0,0 +1,233 @@
+use std::{collections::HashMap, path::PathBuf, time::Duration};
+
+#[cfg(feature = "local-servers")]
+use std::net::SocketAddr;
+
+use holochain_client::InstalledAppId;
+#[cfg(feature = "dpki")]
+use holochain_runtime::DpkiRuntimeConfig;
+#[cfg(feature = "local-servers")]
+use holochain_runtime::LocalServersMode;
+use holochain_runtime::{HolochainRuntimeConfig, NetworkConfig, NetworkProfile};
+use lair_keystore_api::types::SharedLockedArray;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone)]
+pub struct HolochainPluginConfig {
+    /// The config for the holochain runtime
+    pub holochain_runtime_config: HolochainRuntimeConfig,
+
+    /// The Content-Security-Policy for the UIs of the apps, served under the `happ` protocol
+    pub ui_content_security_policy: UiContentSecurityPolicy,
+
+    /// How the UIs of the apps are served to the webviews
+    pub ui_serving_mode: UiServingMode,
+
+    /// When the UIs get their `index.html` for paths that don't match any of their files
+    /// Apps can override it with the `spaFallback` declared in the `happ-ui.json` file at the root of their UI
+    pub ui_spa_fallback: SpaFallback,
+
+    /// The URL scheme whose deep links open the UIs of the apps, as in `{scheme}://happ/{app_id}/{url_path}`
+    /// Only handled with the `deep-link` feature enabled
+    pub deep_link_scheme: Option<String>,
+}
+
+/// When a UI gets its `index.html` for paths that don't match any of its files, so that client-side routing works
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SpaFallback {
+    /// Only for navigation requests: the ones with `Sec-Fetch-Mode: navigate`, that accept `text/html`,
+    /// or whose path has no file extension
+    /// Missing scripts or styles get a `404 Not Found`
+    #[default]
+    NavigationOnly,
+    /// For all requests
+    Always,
+    /// Never, so that all missing paths get a `404 Not Found`
+    Disabled,
+}
+
+/// How the UIs of the apps are served to the webviews
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum UiServingMode {
+    /// Serve the UIs with the `happ` custom protocol, at `happ://{app_id}` (`http://happ.{app_id}` on Windows and Android)
+    #[default]
+    CustomProtocol,
+    /// Serve the UIs over HTTP at `http://{app_id}.localhost:{port}`, only listening on the loopback interface
+    /// Useful on platforms where custom protocols are unreliable, or to open the UIs in an external browser during development
+    LocalhostHttp {
+        /// The port to listen at; if `None`, a free port is picked at launch
+        port: Option<u16>,
+    },
+}
+
+/// The default Content-Security-Policy for the UIs of the apps
+/// Only allows loading resources from the app's own origin, and connecting to its app websocket and to the Tauri IPC
+pub const DEFAULT_UI_CONTENT_SECURITY_POLICY: &'static str = "default-src 'self' {happ_origin}; script-src 'self' {happ_origin} 'wasm-unsafe-eval'; style-src 'self' {happ_origin} 'unsafe-inline'; img-src 'self' {happ_origin} data: blob:; media-src 'self' {happ_origin} data: blob:; font-src 'self' {happ_origin} data:; connect-src 'self' {happ_origin} ipc: http://ipc.localhost {app_websocket_urls}; object-src 'none'; base-uri 'self'; form-action 'self'";
+
+/// The Content-Security-Policy for the UIs of the apps
+/// Policies can use the `{happ_origin}` placeholder for the origin of the app's UI,
+/// and `{app_websocket_urls}` for the URLs of the app websockets the UI can connect to
+///
+/// The policy for an app is the first one of:
+/// its entry in `app_policies`, the `contentSecurityPolicy` declared in the `happ-ui.json` file at the root of its UI
+/// if `allow_app_declared_policies` is set, `default_policy`, or `DEFAULT_UI_CONTENT_SECURITY_POLICY`
+#[derive(Clone, Debug, Default)]
+pub struct UiContentSecurityPolicy {
+    /// The policy for all apps that don't declare their own
+    pub default_policy: Option<String>,
+
+    /// Policies for specific apps, which override the ones declared by the apps themselves
+    pub app_policies: HashMap<InstalledAppId, String>,
+
+    /// Only report the violations of the policy instead of blocking them, to migrate existing UIs
+    pub report_only: bool,
+
+    /// Let the apps loosen the policy with the one declared in their `happ-ui.json`
+    /// Off by default, since a malicious UI could otherwise declare a policy that allows anything
+    pub allow_app_declared_policies: bool,
+}
+
+impl UiContentSecurityPolicy {
+    /// The policy for the given app, before replacing its placeholders
+    ///
+    /// * `app_id` - the app whose policy to get, compared case-insensitively since webviews lowercase the hosts
+    /// * `declared_policy` - the policy declared by the app in its `happ-ui.json`, ignored unless `allow_app_declared_policies` is set
+    pub fn policy_for_app(&self, app_id: &str, declared_policy: Option<String>) -> String {
+        self.app_policies
+            .iter()
+            .find(|(id, _)| id.eq_ignore_ascii_case(app_id))
+            .map(|(_, policy)| policy.clone())
+            .or(declared_policy.filter(|_| self.allow_app_declared_policies))
+            .or(self.default_policy.clone())
+            .unwrap_or(DEFAULT_UI_CONTENT_SECURITY_POLICY.to_string())
+    }
+}
+
+impl From<HolochainRuntimeConfig> for HolochainPluginConfig {
+    fn from(holochain_runtime_config: HolochainRuntimeConfig) -> Self {
+        Self {
+            holochain_runtime_config,
+            ui_content_security_policy: UiContentSecurityPolicy::default(),
+            ui_serving_mode: UiServingMode::default(),
+            ui_spa_fallback: SpaFallback::default(),
+            deep_link_scheme: None,
+        }
+    }
+}
+
+/// The builder methods of `HolochainRuntimeConfig` are forwarded so that the plugin can be configured in one chain
+impl HolochainPluginConfig {
+    pub fn new(holochain_dir: PathBuf, network_config: NetworkConfig) -> Self {
+        HolochainRuntimeConfig::new(holochain_dir, network_config).into()
+    }
+
+    fn map_runtime_config(
+        mut self,
+        map: impl FnOnce(HolochainRuntimeConfig) -> HolochainRuntimeConfig,
+    ) -> Self {
+        self.holochain_runtime_config = map(self.holochain_runtime_config);
+        self
+    }
+
+    pub fn admin_port(self, admin_port: u16) -> Self {
+        self.map_runtime_config(|config| config.admin_port(admin_port))
+    }
+
+    pub fn network_profile(self, network_profile: NetworkProfile) -> Self {
+        self.map_runtime_config(|config| config.network_profile(network_profile))
+    }
+
+    pub fn enable_mdns_discovery(self) -> Self {
+        self.map_runtime_config(|config| config.enable_mdns_discovery())
+    }
+
+    pub fn restore_device_seed_backup(
+        self,
+        backup: Vec<u8>,
+        passphrase: SharedLockedArray,
+    ) -> Self {
+        self.map_runtime_config(|config| config.restore_device_seed_backup(backup, passphrase))
+    }
+
+    pub fn shutdown_timeout(self, shutdown_timeout: Duration) -> Self {
+        self.map_runtime_config(|config| config.shutdown_timeout(shutdown_timeout))
+    }
+
+    /// See `HolochainRuntimeConfig::spawn_local_servers()`
+    #[cfg(feature = "local-servers")]
+    pub fn spawn_local_servers(self, listen_address: SocketAddr) -> Self {
+        self.map_runtime_config(|config| config.spawn_local_servers(listen_address))
+    }
+
+    /// See `HolochainRuntimeConfig::local_servers_mode()`
+    #[cfg(feature = "local-servers")]
+    pub fn local_servers_mode(self, local_servers_mode: LocalServersMode) -> Self {
+        self.map_runtime_config(|config| config.local_servers_mode(local_servers_mode))
+    }
+
+    #[cfg(feature = "dpki")]
+    pub fn enable_dpki(self, dpki: DpkiRuntimeConfig) -> Self {
+        self.map_runtime_config(|config| config.enable_dpki(dpki))
+    }
+
+    pub fn ui_content_security_policy(
+        mut self,
+        ui_content_security_policy: UiContentSecurityPolicy,
+    ) -> Self {
+        self.ui_content_security_policy = ui_content_security_policy;
+        self
+    }
+
+    pub fn ui_serving_mode(mut self, ui_serving_mode: UiServingMode) -> Self {
+        self.ui_serving_mode = ui_serving_mode;
+        self
+    }
+
+    pub fn ui_spa_fallback(mut self, ui_spa_fallback: SpaFallback) -> Self {
+        self.ui_spa_fallback = ui_spa_fallback;
+        self
+    }
+
+    pub fn deep_link_scheme(mut self, deep_link_scheme: String) -> Self {
+        self.deep_link_scheme = Some(deep_link_scheme);
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_uses_the_declared_policies_when_allowed() {
+        let declared_policy = || Some(String::from("default-src *"));
+        let mut policy = UiContentSecurityPolicy::default();
+
+        assert_eq!(
+            policy.policy_for_app("forum", declared_policy()),
+            DEFAULT_UI_CONTENT_SECURITY_POLICY
+        );
+
+        policy.default_policy = Some(String::from("default-src 'self'"));
+        assert_eq!(
+            policy.policy_for_app("forum", declared_policy()),
+            "default-src 'self'"
+        );
+
+        policy.allow_app_declared_policies = true;
+        assert_eq!(
+            policy.policy_for_app("forum", declared_policy()),
+            "default-src *"
+        );
+        assert_eq!(policy.policy_for_app("forum", None), "default-src 'self'");
+
+        policy
+            .app_policies
+            .insert(String::from("Forum"), String::from("default-src 'none'"));
+        assert_eq!(
+            policy.policy_for_app("forum", declared_policy()),
+            "default-src 'none'"
+        );
+    }
+}
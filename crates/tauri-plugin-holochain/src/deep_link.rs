@@ -0,0 +1,164 @@
+use holochain_client::AppInfo;
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Manager, Runtime};
+use tauri_plugin_deep_link::{DeepLink, DeepLinkExt};
+use url::Url;
+
+use crate::{happ_origin, HolochainExt};
+
+/// The host of the deep links into the UIs of the apps, as in `{scheme}://happ/{app_id}/{url_path}`
+const HAPP_DEEP_LINK_HOST: &'static str = "happ";
+
+/// A deep link into the UI of an app
+/// Payload of the `holochain://deep-link-app-not-installed` event
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct HappDeepLink {
+    pub app_id: String,
+    /// The path to open in the UI of the app, with its query and fragment
+    pub url_path: String,
+}
+
+/// Parses a `{scheme}://happ/{app_id}/{url_path}` deep link, returning `None` for any other URL
+fn parse_happ_deep_link(scheme: &str, url: &Url) -> Option<HappDeepLink> {
+    if !url.scheme().eq_ignore_ascii_case(scheme) || url.host_str() != Some(HAPP_DEEP_LINK_HOST) {
+        return None;
+    }
+
+    let mut segments = url.path_segments()?;
+    let app_id = segments.next().filter(|segment| !segment.is_empty())?;
+    let app_id = percent_encoding::percent_decode_str(app_id)
+        .decode_utf8()
+        .ok()?
+        .to_string();
+
+    let mut url_path = segments.collect::<Vec<&str>>().join("/");
+    if let Some(query) = url.query() {
+        url_path.push_str(format!("?{query}").as_str());
+    }
+    if let Some(fragment) = url.fragment() {
+        url_path.push_str(format!("#{fragment}").as_str());
+    }
+
+    Some(HappDeepLink { app_id, url_path })
+}
+
+/// Focuses the window for the app of the deep link, navigating it to its path, or opens a new one if there is none
+/// Emits `holochain://deep-link-app-not-installed` if the app is not installed
+async fn open_happ_deep_link<R: Runtime>(
+    app_handle: &AppHandle<R>,
+    deep_link: HappDeepLink,
+) -> crate::Result<()> {
+    let holochain = app_handle.holochain()?;
+
+    let apps: Vec<AppInfo> = holochain
+        .admin_websocket()
+        .await?
+        .list_apps(None)
+        .await
+        .map_err(|err| crate::Error::ConductorApiError(err))?;
+    if !apps
+        .iter()
+        .any(|app| app.installed_app_id.eq(&deep_link.app_id))
+    {
+        log::warn!(
+            "Received a deep link for app {} which is not installed.",
+            deep_link.app_id
+        );
+        app_handle.emit("holochain://deep-link-app-not-installed", deep_link)?;
+        return Ok(());
+    }
+
+    if let Some(window) =
+        app_handle.get_webview_window(&holochain.web_happ_window_label(&deep_link.app_id))
+    {
+        let origin = happ_origin(&holochain.ui_serving_mode, &deep_link.app_id);
+        let url = Url::parse(format!("{origin}/{}", deep_link.url_path).as_str())?;
+        window.navigate(url)?;
+        window.set_focus()?;
+        return Ok(());
+    }
+
+    holochain
+        .web_happ_window_builder(deep_link.app_id, Some(deep_link.url_path))
+        .await?
+        .build()?;
+
+    Ok(())
+}
+
+fn handle_deep_links<R: Runtime>(app_handle: &AppHandle<R>, scheme: &String, urls: Vec<Url>) {
+    for url in urls {
+        let Some(deep_link) = parse_happ_deep_link(scheme, &url) else {
+            log::debug!("Ignoring deep link {url}.");
+            continue;
+        };
+        let app_handle = app_handle.clone();
+        tauri::async_runtime::spawn(async move {
+            if let Err(err) = open_happ_deep_link(&app_handle, deep_link).await {
+                log::error!("Failed to open deep link {url}: {err:?}");
+            }
+        });
+    }
+}
+
+/// Opens the UIs of the apps for the `{scheme}://happ/{app_id}/{url_path}` deep links,
+/// including the one the app was launched with
+/// Requires `tauri_plugin_deep_link` to be initialized in the app, with the scheme in its config on macOS and mobile
+pub(crate) fn setup_deep_links<R: Runtime>(
+    app_handle: &AppHandle<R>,
+    scheme: String,
+) -> crate::Result<()> {
+    if app_handle.try_state::<DeepLink<R>>().is_none() {
+        return Err(crate::Error::DeepLinkPluginNotInitialized);
+    }
+
+    // Windows and Linux only know about the scheme once it's registered at runtime
+    #[cfg(any(windows, target_os = "linux"))]
+    app_handle.deep_link().register(scheme.as_str())?;
+
+    if let Some(urls) = app_handle.deep_link().get_current()? {
+        handle_deep_links(app_handle, &scheme, urls);
+    }
+
+    let handle = app_handle.clone();
+    app_handle.deep_link().on_open_url(move |event| {
+        handle_deep_links(&handle, &scheme, event.urls());
+    });
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(url: &str) -> Option<HappDeepLink> {
+        parse_happ_deep_link("myapp", &Url::parse(url).unwrap())
+    }
+
+    #[test]
+    fn parses_happ_deep_links() {
+        assert_eq!(
+            parse("myapp://happ/forum/posts/123?sort=new#replies"),
+            Some(HappDeepLink {
+                app_id: String::from("forum"),
+                url_path: String::from("posts/123?sort=new#replies"),
+            })
+        );
+        assert_eq!(
+            parse("myapp://happ/My%20Forum"),
+            Some(HappDeepLink {
+                app_id: String::from("My Forum"),
+                url_path: String::new(),
+            })
+        );
+    }
+
+    #[test]
+    fn ignores_other_deep_links() {
+        assert_eq!(parse("otherapp://happ/forum/posts"), None);
+        assert_eq!(parse("myapp://settings/forum"), None);
+        assert_eq!(parse("myapp://happ/"), None);
+    }
+}
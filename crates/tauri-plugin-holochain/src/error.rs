@@ -1,3 +1,5 @@
+use std::path::PathBuf;
+
 use holochain_client::ConductorApiError;
 use serde::{ser::Serializer, Serialize};
 
@@ -21,6 +23,9 @@ pub enum Error {
     #[error(transparent)]
     UrlParseError(#[from] url::ParseError),
 
+    #[error(transparent)]
+    SerdeJsonError(#[from] serde_json::Error),
+
     #[error("ConductorApiError: `{0:?}`")]
     ConductorApiError(ConductorApiError),
 
@@ -33,6 +38,12 @@ pub enum Error {
     #[error("Http server error: {0}")]
     HttpServerError(String),
 
+    #[error(transparent)]
+    HttpError(#[from] tauri::http::Error),
+
+    #[error("Asset {0} is outside of the UI directory")]
+    AssetOutsideOfUi(String),
+
     #[error("Sign zome call error: {0}")]
     SignZomeCallError(String),
 
@@ -44,6 +55,32 @@ pub enum Error {
 
     #[error("Holochain has not been initialized yet")]
     HolochainNotInitializedError,
+
+    #[error("Holochain was not initialized in a locked state")]
+    HolochainNotLockedError,
+
+    #[error("There is no holochain runtime named \"{0}\"")]
+    RuntimeNotFound(String),
+
+    #[error("The holochain runtime \"{0}\" is already running in another directory")]
+    RuntimeNameAlreadyInUse(String),
+
+    #[error("The name \"{0}\" is reserved for the default holochain runtime")]
+    ReservedRuntimeName(String),
+
+    #[error("The holochain runtime \"{0}\" is already running in {1:?}")]
+    HolochainDirAlreadyInUse(String, PathBuf),
+
+    #[error("The window \"{0}\" was not built by any holochain runtime")]
+    NotAHappWindow(String),
+
+    #[cfg(feature = "deep-link")]
+    #[error(transparent)]
+    DeepLinkError(#[from] tauri_plugin_deep_link::Error),
+
+    #[cfg(feature = "deep-link")]
+    #[error("The deep-link plugin has not been initialized in the app")]
+    DeepLinkPluginNotInitialized,
 }
 
 impl Serialize for Error {
@@ -0,0 +1,109 @@
+use holochain_runtime::UiContent;
+use serde::Deserialize;
+
+use crate::{http_server::read_ui_file, SpaFallback};
+
+/// File at the root of a UI where the app declares how it needs to be served
+pub(crate) const HAPP_UI_MANIFEST_FILE: &'static str = "happ-ui.json";
+
+/// The page served with `404 Not Found` for missing paths, if the UI has it and doesn't declare another one
+const DEFAULT_NOT_FOUND_PAGE: &'static str = "404.html";
+
+/// The contents of the `happ-ui.json` file
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct HappUiManifest {
+    /// The Content-Security-Policy that the UI needs, with the same placeholders as `UiContentSecurityPolicy`
+    pub content_security_policy: Option<String>,
+    /// Overrides `HolochainPluginConfig::ui_spa_fallback` for this UI
+    pub spa_fallback: Option<SpaFallback>,
+    /// The page served with `404 Not Found` for missing paths, `404.html` by default
+    pub not_found_page: Option<String>,
+    /// Paths served with another file of the UI, checked in order when the requested path doesn't exist
+    #[serde(default)]
+    pub rewrites: Vec<UiRewrite>,
+}
+
+/// Serves `destination` for the paths matching `source`
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct UiRewrite {
+    /// Either an exact path, or a prefix ending in `*`, e.g. `/docs/*`
+    pub source: String,
+    pub destination: String,
+}
+
+fn trim_leading_slash(path: &str) -> &str {
+    path.trim_start_matches('/')
+}
+
+impl UiRewrite {
+    fn matches(&self, path: &str) -> bool {
+        let source = trim_leading_slash(&self.source);
+        match source.strip_suffix('*') {
+            Some(prefix) => path.starts_with(prefix),
+            None => path.eq(source),
+        }
+    }
+}
+
+impl HappUiManifest {
+    /// The file to serve for the given path according to the `rewrites`, if any of them matches it
+    pub fn rewrite(&self, path: &str) -> Option<&str> {
+        self.rewrites
+            .iter()
+            .find(|rewrite| rewrite.matches(path))
+            .map(|rewrite| trim_leading_slash(&rewrite.destination))
+    }
+
+    pub fn not_found_page(&self) -> &str {
+        self.not_found_page
+            .as_ref()
+            .map(|page| trim_leading_slash(page))
+            .unwrap_or(DEFAULT_NOT_FOUND_PAGE)
+    }
+}
+
+/// Reads the `happ-ui.json` file from the given UI, if it has one
+pub(crate) fn read_happ_ui_manifest(content: &UiContent) -> crate::Result<HappUiManifest> {
+    let Some(manifest) = read_ui_file(content, HAPP_UI_MANIFEST_FILE)? else {
+        return Ok(HappUiManifest::default());
+    };
+    Ok(serde_json::from_slice(&manifest)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn manifest(json: &str) -> HappUiManifest {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn rewrites_matching_paths() {
+        let manifest = manifest(
+            r#"{
+                "rewrites": [
+                    { "source": "/docs/*", "destination": "/docs/index.html" },
+                    { "source": "/about", "destination": "/about.html" }
+                ]
+            }"#,
+        );
+
+        assert_eq!(manifest.rewrite("docs/intro"), Some("docs/index.html"));
+        assert_eq!(manifest.rewrite("about"), Some("about.html"));
+        assert_eq!(manifest.rewrite("about/team"), None);
+        assert_eq!(manifest.not_found_page(), "404.html");
+    }
+
+    #[test]
+    fn parses_declared_fallback() {
+        let manifest =
+            manifest(r#"{ "spaFallback": "disabled", "notFoundPage": "/errors/404.html" }"#);
+
+        assert_eq!(manifest.spa_fallback, Some(SpaFallback::Disabled));
+        assert_eq!(manifest.not_found_page(), "errors/404.html");
+        assert!(manifest.rewrites.is_empty());
+    }
+}
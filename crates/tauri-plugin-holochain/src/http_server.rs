@@ -1,200 +1,792 @@
-// use std::net::SocketAddr;
+use std::{
+    convert::Infallible,
+    io::{Read, Seek, SeekFrom},
+    net::SocketAddr,
+    path::{Component, Path, PathBuf},
+    sync::Arc,
+};
 
-// use http_body_util::Full;
-// use hyper::body::Bytes;
-// use hyper::server::conn::http1;
-// use hyper::service::service_fn;
-// use hyper::{Response, StatusCode};
-// use hyper_util::rt::TokioIo;
-// use tauri::{AppHandle, Runtime};
-// use tokio::net::TcpListener;
+use holochain_client::InstalledAppId;
+use holochain_runtime::{FileSystem, InstalledUi, UiArchive, UiContent};
+use http_body_util::{combinators::UnsyncBoxBody, BodyExt, Full, StreamBody};
+use hyper::{
+    body::{Bytes, Frame},
+    server::conn::http1,
+    service::service_fn,
+};
+use hyper_util::rt::TokioIo;
+use serde::Serialize;
+use tauri::{
+    http::{header, request, response, uri::Authority, Method, Request, Response, StatusCode, Uri},
+    Runtime,
+};
+use tokio::net::TcpListener;
 
-use holochain_runtime::FileSystem;
+use crate::{
+    happ_ui_manifest::HappUiManifest, router::handle_request, HolochainPlugin, SpaFallback,
+    UiServingMode,
+};
+
+/// Size of the chunks in which the localhost server streams the assets
+const STREAM_CHUNK_SIZE: usize = 64 * 1024;
+
+/// How many chunks of an asset are read ahead of the ones the localhost server has sent
+const STREAM_READ_AHEAD_CHUNKS: usize = 4;
 
 pub fn pong_iframe() -> String {
     format!("<html><head></head><body><script>window.onload = () => window.parent.postMessage('pong', '*') </script></body></html>")
 }
 
-// pub fn window_html() -> String {
-//     include_str!("../../packages/frame/dist/index.html").into()
-// }
-
-// pub async fn start_http_server<R: Runtime>(
-//     app_handle: AppHandle<R>,
-//     ui_server_port: u16,
-// ) -> crate::Result<()> {
-//     let addr: SocketAddr = ([127, 0, 0, 1], ui_server_port).into();
-//     let listener = TcpListener::bind(addr).await?;
-//     tauri::async_runtime::spawn(async move {
-//         let app_handle = app_handle.clone();
-//         // The closure inside `make_service_fn` is run for each connection,
-//         // creating a 'service' to handle requests for that specific connection.
-//         // let make_service = make_service_fn(move |_| async {
-//         //     // While the state was moved into the make_service closure,
-//         //     // we need to clone it here because this closure is called
-//         //     // once for every connection.
-//         //     //
-//         //     // Each connection could send multiple requests, so
-//         //     // the `Service` needs a clone to handle later requests.
-//         //     // This is the `Service` that will handle the connection.
-//         //     // `service_fn` is a helper to convert a function that
-//         //     // returns a Response into a `Service`.
-//         //     // let app_handle = app_handle.clone();
-//         //     Ok::<_, hyper::Error>(service_fn(move |request| {
-//         //         let app_handle = app_handle.clone();
-//         //         async move {}
-//         //     }))
-//         // });
-
-//         loop {
-//             let app_handle = app_handle.clone();
-//             if let Ok((stream, _)) = listener.accept().await {
-//                 let io = TokioIo::new(stream);
-
-//                 // This is the `Service` that will handle the connection.
-//                 // `service_fn` is a helper to convert a function that
-//                 // returns a Response into a `Service`.
-//                 let service = service_fn(move |request| {
-//                     let app_handle = app_handle.clone();
-//                     async move {
-//                         let host = request
-//                             .headers()
-//                             .get("host")
-//                             .expect("URI has no host")
-//                             // .ok_or(crate::Error::HttpServerError(String::from(
-//                             //     "URI has no host",
-//                             // )))?
-//                             .clone()
-//                             .to_str()
-//                             .expect("Could not convert str to string")
-//                             // .map_err(|err| crate::Error::HttpServerError(format!("{:?}", err)))?
-//                             .to_string();
-
-//                         if host.starts_with("ping.localhost") {
-//                             return Response::builder()
-//                                 .status(202)
-//                                 .header("content-type", "text/html")
-//                                 .body(pong_iframe().into());
-//                         }
-//                         if host.starts_with("localhost") {
-//                             return Response::builder()
-//                                 .status(202)
-//                                 .header("content-type", "text/html")
-//                                 .body(window_html().into());
-//                         }
-
-//                         let split_host: Vec<String> =
-//                             host.split(".").into_iter().map(|s| s.to_string()).collect();
-//                         let lowercase_app_id = split_host.get(0).expect("Failed to get the app id");
-
-//                         let file_name = request.uri().path();
-
-//                         let Ok(holochain) = app_handle.holochain() else {
-//                             return Response::builder()
-//                                 .status(StatusCode::INTERNAL_SERVER_ERROR)
-//                                 .body(
-//                                     format!("Called http UI before initializing holochain")
-//                                         .as_bytes()
-//                                         .to_vec()
-//                                         .into(),
-//                                 );
-//                         };
-
-//                         let r: hyper::http::Result<Response<Full<Bytes>>> = match read_asset(
-//                             &holochain.holochain_runtime.filesystem,
-//                             &lowercase_app_id,
-//                             file_name.to_string(),
-//                         )
-//                         .await
-//                         {
-//                             Ok(Some((asset, mime_type))) => {
-//                                 let mut response_builder = Response::builder().status(202);
-//                                 if let Some(mime_type) = mime_type {
-//                                     response_builder =
-//                                         response_builder.header("content-type", mime_type);
-//                                 }
-
-//                                 response_builder.body(asset.into())
-//                             }
-//                             Ok(None) => Response::builder().status(404).body(vec![].into()),
-//                             Err(e) => Ok(Response::builder()
-//                                 .status(500)
-//                                 .body(format!("{:?}", e).into())
-//                                 .expect("Failed to build body of error response")),
-//                         };
-//                         // admin_ws.close();
-//                         r
-//                     }
-//                     // Get the current count, and also increment by 1, in a single
-//                     // // atomic operation.
-//                     // let count = counter.fetch_add(1, Ordering::AcqRel);
-//                     // async move {
-//                     //     Ok::<_, Error>(Response::new(Full::new(Bytes::from(format!(
-//                     //         "Request #{}",
-//                     //         count
-//                     //     )))))
-//                     // }
-//                 });
-
-//                 if let Err(err) = http1::Builder::new().serve_connection(io, service).await {
-//                     println!("Error serving connection: {:?}", err);
-//                 }
-//             } else {
-//                 log::error!("Could not accept incoming tcp stream");
-//             }
-//         }
-//     });
-
-//     Ok(())
-// }
-
-pub async fn read_asset(
-    fs: &FileSystem,
-    app_id: &String,
-    mut asset_name: String,
-) -> crate::Result<Option<(Vec<u8>, Option<String>)>> {
-    log::debug!("Reading asset from filesystem. Asset name: {}", asset_name);
-    if asset_name.starts_with("/") {
-        asset_name = asset_name
-            .strip_prefix("/")
-            .expect("Failed to strip prefix")
-            .to_string();
+/// Rebuilds the full URL of a request to the localhost server from its `Host` header,
+/// rejecting requests for other hosts or ports, e.g. through DNS rebinding,
+/// and cross-origin requests from websites or from the UIs of other apps
+fn localhost_request_uri(request: &request::Parts, port: u16) -> Result<Uri, String> {
+    let host = request
+        .headers
+        .get(header::HOST)
+        .and_then(|host| host.to_str().ok())
+        .ok_or(String::from("Request has no host"))?;
+    let authority: Authority = host
+        .parse()
+        .map_err(|err| format!("Invalid host {host}: {err}"))?;
+    if authority.port_u16().ne(&Some(port)) || !authority.host().ends_with(".localhost") {
+        return Err(format!("Invalid host {host}"));
+    }
+
+    if let Some(origin) = request.headers.get(header::ORIGIN) {
+        let expected_origin = format!("http://{}", authority.as_str().to_lowercase());
+        if origin.to_str().ok().ne(&Some(expected_origin.as_str())) {
+            return Err(format!("Invalid origin {origin:?} for host {host}"));
+        }
+    }
+
+    let path_and_query = request
+        .uri
+        .path_and_query()
+        .map(|path_and_query| path_and_query.as_str())
+        .unwrap_or("/");
+    format!("http://{host}{path_and_query}")
+        .parse()
+        .map_err(|err| format!("Invalid URL: {err}"))
+}
+
+/// Streams the contents of the reader in chunks, reading them in the blocking thread pool
+fn stream_body(mut reader: Box<dyn Read + Send>) -> UnsyncBoxBody<Bytes, std::io::Error> {
+    let (sender, receiver) = tokio::sync::mpsc::channel(STREAM_READ_AHEAD_CHUNKS);
+    tauri::async_runtime::spawn_blocking(move || loop {
+        let mut buffer = vec![0; STREAM_CHUNK_SIZE];
+        let chunk = match reader.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(read) => {
+                buffer.truncate(read);
+                Ok(Frame::data(Bytes::from(buffer)))
+            }
+            Err(err) => Err(err),
+        };
+        let failed = chunk.is_err();
+        // Stop reading if the connection was closed
+        if sender.blocking_send(chunk).is_err() || failed {
+            break;
+        }
+    });
+
+    let chunks = futures::stream::unfold(receiver, |mut receiver| async move {
+        receiver.recv().await.map(|chunk| (chunk, receiver))
+    });
+    StreamBody::new(chunks).boxed_unsync()
+}
+
+async fn handle_localhost_request<R: Runtime>(
+    holochain: Arc<HolochainPlugin<R>>,
+    port: u16,
+    request: Request<hyper::body::Incoming>,
+) -> Response<UnsyncBoxBody<Bytes, std::io::Error>> {
+    let (mut parts, _body) = request.into_parts();
+    let response = match localhost_request_uri(&parts, port) {
+        Ok(uri) => {
+            parts.uri = uri;
+            // UIs only get assets, so the body of the request is not needed
+            handle_request(Ok(holochain), Request::from_parts(parts, vec![])).await
+        }
+        Err(reason) => {
+            log::warn!("Rejected request to the localhost UI server: {reason}");
+            response::Builder::new()
+                .status(StatusCode::FORBIDDEN)
+                .body(reason.into_bytes().into())
+                .expect("Failed to build forbidden response")
+        }
+    };
+    response.map(|body| match body {
+        ResponseBody::Bytes(bytes) => Full::new(Bytes::from(bytes))
+            .map_err(|never| match never {})
+            .boxed_unsync(),
+        ResponseBody::Stream(reader) => stream_body(reader),
+    })
+}
+
+/// The server for `UiServingMode::LocalhostHttp`, which serves the UIs of the apps at `http://{app_id}.localhost:{port}`
+/// Bound before the runtime is launched, so that the launch fails if the port is not available
+pub(crate) struct LocalhostUiServer {
+    listener: TcpListener,
+    port: u16,
+}
+
+impl LocalhostUiServer {
+    /// Binds the server to the given port on the loopback interface
+    ///
+    /// * `port` - the port to listen at; if `None`, a free port is picked
+    pub(crate) async fn bind(port: Option<u16>) -> crate::Result<Self> {
+        let address: SocketAddr = ([127, 0, 0, 1], port.unwrap_or(0)).into();
+        let listener = TcpListener::bind(address).await.map_err(|err| {
+            crate::Error::HttpServerError(format!(
+                "Failed to bind the localhost UI server to {address}: {err:?}"
+            ))
+        })?;
+        let port = listener.local_addr()?.port();
+        Ok(Self { listener, port })
+    }
+
+    /// The `UiServingMode` with the port the server is bound to
+    pub(crate) fn ui_serving_mode(&self) -> UiServingMode {
+        UiServingMode::LocalhostHttp {
+            port: Some(self.port),
+        }
+    }
+
+    /// Serves the UIs of the apps of the given runtime, handling the requests with the same router as the `happ` protocol
+    pub(crate) fn serve<R: Runtime>(self, holochain: Arc<HolochainPlugin<R>>) {
+        let Self { listener, port } = self;
+        log::info!("Serving the UIs of the apps at http://{{app_id}}.localhost:{port}.");
+
+        tauri::async_runtime::spawn(async move {
+            loop {
+                let stream = match listener.accept().await {
+                    Ok((stream, _)) => stream,
+                    Err(err) => {
+                        log::error!("Could not accept incoming tcp stream: {err:?}");
+                        continue;
+                    }
+                };
+                let holochain = holochain.clone();
+                tauri::async_runtime::spawn(async move {
+                    let service = service_fn(move |request| {
+                        let holochain = holochain.clone();
+                        async move {
+                            Ok::<_, Infallible>(
+                                handle_localhost_request(holochain, port, request).await,
+                            )
+                        }
+                    });
+                    if let Err(err) = http1::Builder::new()
+                        .serve_connection(TokioIo::new(stream), service)
+                        .await
+                    {
+                        log::warn!("Error serving connection: {err:?}");
+                    }
+                });
+            }
+        });
+    }
+}
+
+/// An asset of the UI of an installed app, resolved to a file in the UI store
+pub struct UiAsset {
+    pub content: AssetContent,
+    pub mime_type: Option<String>,
+    /// Hash of the whole UI, which changes whenever any of its assets change
+    pub ui_hash: String,
+}
+
+/// The body of a response to a request for a UI
+pub enum ResponseBody {
+    Bytes(Vec<u8>),
+    /// The whole contents of an asset, read as they are sent instead of loading them in memory
+    Stream(Box<dyn Read + Send>),
+}
+
+impl ResponseBody {
+    /// Reads the whole body, for the `happ` custom protocol, which can't stream its responses
+    pub fn into_bytes(self) -> std::io::Result<Vec<u8>> {
+        match self {
+            ResponseBody::Bytes(bytes) => Ok(bytes),
+            ResponseBody::Stream(mut reader) => {
+                let mut bytes = Vec::new();
+                reader.read_to_end(&mut bytes)?;
+                Ok(bytes)
+            }
+        }
+    }
+}
+
+impl From<Vec<u8>> for ResponseBody {
+    fn from(bytes: Vec<u8>) -> Self {
+        ResponseBody::Bytes(bytes)
+    }
+}
+
+/// Where the contents of a `UiAsset` are read from
+pub enum AssetContent {
+    /// A file in the directory where a UI installed before UIs were stored as archives was extracted
+    File(PathBuf),
+    /// A file inside the archive of the UI
+    Archived {
+        archive: Arc<UiArchive>,
+        name: String,
+    },
+}
+
+impl AssetContent {
+    fn not_found(name: &String) -> crate::Error {
+        crate::Error::Io(std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            format!("{name} is not in the UI archive"),
+        ))
+    }
+
+    fn len(&self) -> crate::Result<u64> {
+        match self {
+            AssetContent::File(file) => Ok(std::fs::metadata(file)?.len()),
+            AssetContent::Archived { archive, name } => {
+                archive.file_size(name).ok_or(Self::not_found(name))
+            }
+        }
+    }
+
+    fn read_all(&self) -> crate::Result<Vec<u8>> {
+        match self {
+            AssetContent::File(file) => Ok(std::fs::read(file)?),
+            AssetContent::Archived { archive, name } => archive
+                .read(name)
+                .map_err(holochain_runtime::Error::from)?
+                .ok_or(Self::not_found(name)),
+        }
+    }
+
+    /// Opens the asset to stream its contents
+    fn open(&self) -> crate::Result<Box<dyn Read + Send>> {
+        match self {
+            AssetContent::File(file) => Ok(Box::new(std::fs::File::open(file)?)),
+            AssetContent::Archived { archive, name } => archive
+                .open_file(name)
+                .map_err(holochain_runtime::Error::from)?
+                .ok_or(Self::not_found(name)),
+        }
+    }
+
+    fn read_range(&self, start: u64, length: u64) -> crate::Result<Vec<u8>> {
+        match self {
+            AssetContent::File(file) => Ok(read_range(file, start, length)?),
+            AssetContent::Archived { archive, name } => archive
+                .read_range(name, start, length)
+                .map_err(holochain_runtime::Error::from)?
+                .ok_or(Self::not_found(name)),
+        }
+    }
+}
+
+/// Finds the given file in the UI, `None` if it doesn't exist
+pub fn find_ui_file(content: &UiContent, name: &str) -> crate::Result<Option<AssetContent>> {
+    match content {
+        UiContent::Archive(archive) => Ok(archive.contains(name).then(|| AssetContent::Archived {
+            archive: archive.clone(),
+            name: name.to_string(),
+        })),
+        UiContent::Directory(ui_dir) => Ok(sandboxed_path(ui_dir, name)?.map(AssetContent::File)),
+    }
+}
+
+/// Reads the given file from the UI, `None` if it doesn't exist
+pub fn read_ui_file(content: &UiContent, name: &str) -> crate::Result<Option<Vec<u8>>> {
+    find_ui_file(content, name)?
+        .map(|asset_content| asset_content.read_all())
+        .transpose()
+}
+
+/// Resolves the given path inside the UI directory, following symlinks,
+/// and fails with `Error::AssetOutsideOfUi` if it's absolute, has `..` segments,
+/// or ends up outside of the UI directory
+/// Returns `None` if the path doesn't exist or is not a file
+pub fn sandboxed_path(ui_dir: &Path, asset_name: &str) -> crate::Result<Option<PathBuf>> {
+    let outside_of_ui = || crate::Error::AssetOutsideOfUi(asset_name.to_string());
+
+    // Backslashes are separators on Windows, so they could hide `..` segments from the checks below on other platforms
+    if asset_name.contains(['\\', '\0']) {
+        return Err(outside_of_ui());
+    }
+
+    let mut relative_path = PathBuf::new();
+    for component in Path::new(asset_name).components() {
+        match component {
+            Component::Normal(segment) => relative_path.push(segment),
+            Component::CurDir => {}
+            Component::ParentDir | Component::RootDir | Component::Prefix(_) => {
+                return Err(outside_of_ui());
+            }
+        }
+    }
+
+    let candidate = ui_dir.join(relative_path);
+    if !candidate.exists() {
+        return Ok(None);
+    }
+
+    let canonical_ui_dir = ui_dir.canonicalize()?;
+    let canonical_candidate = candidate.canonicalize()?;
+    if !canonical_candidate.starts_with(&canonical_ui_dir) {
+        return Err(outside_of_ui());
+    }
+    if !canonical_candidate.is_file() {
+        return Ok(None);
+    }
+
+    Ok(Some(canonical_candidate))
+}
+
+/// An asset listed in the `UiAssetManifest`
+#[derive(Serialize, Debug, Clone)]
+pub struct UiAssetInfo {
+    /// The path of the asset relative to the root of the UI, as it's requested from the happ origin
+    pub path: String,
+    pub size: u64,
+    pub mime_type: Option<String>,
+}
+
+/// All the assets of the UI of an app, for offline-first UIs to precache them or to check for updates
+/// without a service worker, since they can't be registered under the `happ` protocol
+#[derive(Serialize, Debug, Clone)]
+pub struct UiAssetManifest {
+    pub app_id: InstalledAppId,
+    /// Hash of the whole UI, which changes whenever any of its assets change
+    pub ui_hash: String,
+    pub assets: Vec<UiAssetInfo>,
+}
+
+/// Lists the regular files in the given directory, skipping symlinks so that the listing stays inside of the UI
+fn list_ui_dir(dir: &Path, prefix: &str, files: &mut Vec<(String, u64)>) -> crate::Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let Some(name) = entry
+            .file_name()
+            .to_str()
+            .map(|name| format!("{prefix}{name}"))
+        else {
+            continue;
+        };
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            list_ui_dir(&entry.path(), format!("{name}/").as_str(), files)?;
+        } else if file_type.is_file() {
+            files.push((name, entry.metadata()?.len()));
+        }
+    }
+    Ok(())
+}
+
+/// Builds the `UiAssetManifest` for the UI of the given app
+pub fn ui_asset_manifest(fs: &FileSystem, app_id: &String) -> crate::Result<UiAssetManifest> {
+    let installed_ui = fs.bundle_store.get_ui(app_id)?;
+
+    let mut files: Vec<(String, u64)> = match &installed_ui.content {
+        UiContent::Archive(archive) => archive
+            .file_names()
+            .filter_map(|name| archive.file_size(name).map(|size| (name.clone(), size)))
+            .collect(),
+        UiContent::Directory(ui_dir) => {
+            let mut files = Vec::new();
+            list_ui_dir(ui_dir, "", &mut files)?;
+            files
+        }
+    };
+    files.sort();
+
+    let assets = files
+        .into_iter()
+        .map(|(path, size)| UiAssetInfo {
+            mime_type: guess_mime_type(&path),
+            path,
+            size,
+        })
+        .collect();
+
+    Ok(UiAssetManifest {
+        app_id: installed_ui.app_id,
+        ui_hash: installed_ui.ui_hash,
+        assets,
+    })
+}
+
+fn guess_mime_type(name: &str) -> Option<String> {
+    mime_guess::from_path(name)
+        .first()
+        .map(|mime| mime.essence_str().to_string())
+}
+
+/// What a request for an asset of a UI resolves to
+pub enum ResolvedAsset {
+    Found(UiAsset),
+    /// No file matches the request, with the custom 404 page of the UI if it has one
+    NotFound(Option<UiAsset>),
+}
+
+/// Resolves the given asset of the UI to one of its files, without reading it
+/// Paths that don't match any file are resolved, in order, to `{path}/index.html`,
+/// the `rewrites` declared in the `happ-ui.json` of the UI, and its `index.html` if `spa_fallback` allows it
+///
+/// * `is_navigation` - whether the request is a navigation, for `SpaFallback::NavigationOnly`
+pub fn resolve_asset(
+    installed_ui: &InstalledUi,
+    manifest: &HappUiManifest,
+    spa_fallback: SpaFallback,
+    asset_name: String,
+    is_navigation: bool,
+) -> crate::Result<ResolvedAsset> {
+    log::debug!(
+        "Resolving asset from filesystem. Asset name: {}",
+        asset_name
+    );
+    let mut asset_name = asset_name.as_str();
+    if let Some(stripped) = asset_name.strip_prefix("/") {
+        asset_name = stripped;
     }
     if asset_name == "" {
-        asset_name = String::from("index.html");
+        asset_name = "index.html";
     }
 
-    let assets_path = fs.bundle_store.get_ui_path(&app_id)?;
-    let asset_file = assets_path.join(asset_name);
+    let find = |name: &str| -> crate::Result<Option<UiAsset>> {
+        let Some(content) = find_ui_file(&installed_ui.content, name)? else {
+            return Ok(None);
+        };
+        let mime_type = guess_mime_type(name);
+        if mime_type.is_none() {
+            log::warn!("Could not determine MIME Type of file '{:?}'", name);
+        }
+        Ok(Some(UiAsset {
+            content,
+            mime_type,
+            ui_hash: installed_ui.ui_hash.clone(),
+        }))
+    };
 
-    let mime_guess = mime_guess::from_path(asset_file.clone());
+    if let Some(asset) = find(asset_name)? {
+        return Ok(ResolvedAsset::Found(asset));
+    }
+    if let Some(asset) = find(format!("{asset_name}/index.html").as_str())? {
+        return Ok(ResolvedAsset::Found(asset));
+    }
+    if let Some(destination) = manifest.rewrite(asset_name) {
+        if let Some(asset) = find(destination)? {
+            return Ok(ResolvedAsset::Found(asset));
+        }
+    }
 
-    let mime_type = match mime_guess.first() {
-        Some(mime) => Some(mime.essence_str().to_string()),
-        None => {
-            log::warn!("Could not determine MIME Type of file '{:?}'", asset_file);
-            None
+    let spa_fallback = match spa_fallback {
+        SpaFallback::Always => true,
+        SpaFallback::NavigationOnly => is_navigation,
+        SpaFallback::Disabled => false,
+    };
+    if spa_fallback {
+        if let Some(index) = find("index.html")? {
+            return Ok(ResolvedAsset::Found(index));
         }
+    }
+
+    Ok(ResolvedAsset::NotFound(find(manifest.not_found_page())?))
+}
+
+/// Largest number of bytes served in a single partial response,
+/// so that media and large wasm files are loaded in chunks instead of all at once
+const MAX_RANGE_LENGTH: u64 = 4 * 1024 * 1024;
+
+/// The byte range requested with the `Range` header
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ByteRange {
+    /// No range, or one that we don't support, like multiple ranges: serve the whole file
+    Full,
+    /// Inclusive range of bytes to serve
+    Partial { start: u64, end: u64 },
+    /// The range is outside of the file
+    Unsatisfiable,
+}
+
+fn parse_range(range_header: Option<&str>, len: u64) -> ByteRange {
+    let Some(spec) = range_header.and_then(|value| value.trim().strip_prefix("bytes=")) else {
+        return ByteRange::Full;
+    };
+    if spec.contains(',') {
+        return ByteRange::Full;
+    }
+    let Some((start, end)) = spec.split_once('-') else {
+        return ByteRange::Full;
     };
 
-    match std::fs::read(asset_file.clone()) {
-        Ok(asset) => Ok(Some((asset, mime_type))),
-        Err(_e) => {
-            // Fallback to "index.html" to support push-based client-side routing without hashing
-            let asset_file = assets_path.join(String::from("index.html"));
-            let mime_guess = mime_guess::from_path(asset_file.clone());
-
-            let mime_type = match mime_guess.first() {
-                Some(mime) => Some(mime.essence_str().to_string()),
-                None => {
-                    log::warn!("Could not determine MIME Type of file '{:?}'", asset_file);
-                    None
-                }
+    let (start, end) = match (start.trim(), end.trim()) {
+        ("", suffix_length) => {
+            let Ok(suffix_length) = suffix_length.parse::<u64>() else {
+                return ByteRange::Full;
             };
-            match std::fs::read(asset_file.clone()) {
-                Ok(asset) => Ok(Some((asset, mime_type))),
-                Err(_e) => Ok(None)
+            if suffix_length == 0 {
+                return ByteRange::Unsatisfiable;
             }
-        },
+            (len.saturating_sub(suffix_length), u64::MAX)
+        }
+        (start, "") => {
+            let Ok(start) = start.parse::<u64>() else {
+                return ByteRange::Full;
+            };
+            (start, u64::MAX)
+        }
+        (start, end) => {
+            let (Ok(start), Ok(end)) = (start.parse::<u64>(), end.parse::<u64>()) else {
+                return ByteRange::Full;
+            };
+            if end < start {
+                return ByteRange::Full;
+            }
+            (start, end)
+        }
+    };
+
+    if start >= len {
+        return ByteRange::Unsatisfiable;
+    }
+
+    ByteRange::Partial {
+        start,
+        end: end.min(len - 1).min(start + MAX_RANGE_LENGTH - 1),
+    }
+}
+
+/// Only text types get a charset, binary ones like images or wasm must not have it
+fn content_type(mime_type: &Option<String>) -> String {
+    let Some(mime_type) = mime_type else {
+        return String::from("application/octet-stream");
+    };
+    let is_text = mime_type.starts_with("text/")
+        || matches!(
+            mime_type.as_str(),
+            "application/javascript"
+                | "application/json"
+                | "application/xml"
+                | "application/manifest+json"
+                | "image/svg+xml"
+        );
+    if is_text {
+        format!("{mime_type};charset=utf-8")
+    } else {
+        mime_type.clone()
+    }
+}
+
+fn etag_matches(if_none_match: Option<&str>, etag: &String) -> bool {
+    let Some(if_none_match) = if_none_match else {
+        return false;
+    };
+    if_none_match.split(',').any(|candidate| {
+        let candidate = candidate.trim();
+        candidate.eq("*") || candidate.trim_start_matches("W/").eq(etag)
+    })
+}
+
+fn read_range(file: &Path, start: u64, length: u64) -> std::io::Result<Vec<u8>> {
+    let mut file = std::fs::File::open(file)?;
+    file.seek(SeekFrom::Start(start))?;
+    let mut bytes = Vec::with_capacity(length as usize);
+    file.take(length).read_to_end(&mut bytes)?;
+    Ok(bytes)
+}
+
+/// Builds the response serving the given asset:
+/// `304 Not Modified` if the browser has the current version of the UI cached,
+/// `206 Partial Content` for `Range` requests, and `200 OK` otherwise, whose body is streamed
+pub fn asset_response(
+    request: &Request<Vec<u8>>,
+    asset: UiAsset,
+) -> crate::Result<Response<ResponseBody>> {
+    let etag = format!("\"{}\"", asset.ui_hash);
+    let header = |name: header::HeaderName| {
+        request
+            .headers()
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+    };
+
+    let response = response::Builder::new()
+        .header(header::ETAG, etag.as_str())
+        .header(header::CACHE_CONTROL, "no-cache")
+        .header(header::ACCEPT_RANGES, "bytes");
+
+    if etag_matches(header(header::IF_NONE_MATCH), &etag) {
+        return Ok(response
+            .status(StatusCode::NOT_MODIFIED)
+            .body(vec![].into())?);
+    }
+
+    let len = asset.content.len()?;
+    let response = response.header(header::CONTENT_TYPE, content_type(&asset.mime_type));
+    let is_head = request.method().eq(&Method::HEAD);
+
+    match parse_range(header(header::RANGE), len) {
+        ByteRange::Unsatisfiable => Ok(response
+            .status(StatusCode::RANGE_NOT_SATISFIABLE)
+            .header(header::CONTENT_RANGE, format!("bytes */{len}"))
+            .body(vec![].into())?),
+        ByteRange::Partial { start, end } => {
+            let length = end - start + 1;
+            let body = if is_head {
+                vec![]
+            } else {
+                asset.content.read_range(start, length)?
+            };
+            Ok(response
+                .status(StatusCode::PARTIAL_CONTENT)
+                .header(header::CONTENT_RANGE, format!("bytes {start}-{end}/{len}"))
+                .header(header::CONTENT_LENGTH, length)
+                .body(body.into())?)
+        }
+        ByteRange::Full => {
+            let body = if is_head {
+                vec![].into()
+            } else {
+                ResponseBody::Stream(asset.content.open()?)
+            };
+            Ok(response
+                .status(StatusCode::OK)
+                .header(header::CONTENT_LENGTH, len)
+                .body(body)?)
+        }
+    }
+}
+
+/// Builds the `404 Not Found` response with the custom 404 page of the UI
+pub fn not_found_page_response(page: UiAsset) -> crate::Result<Response<Vec<u8>>> {
+    Ok(response::Builder::new()
+        .status(StatusCode::NOT_FOUND)
+        .header(header::CONTENT_TYPE, content_type(&page.mime_type))
+        .header(header::CACHE_CONTROL, "no-cache")
+        .body(page.content.read_all()?)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ui_dir() -> (tempfile::TempDir, PathBuf) {
+        let data_dir = tempfile::tempdir().unwrap();
+        let ui_dir = data_dir.path().join("uis").join("ui-hash");
+        std::fs::create_dir_all(ui_dir.join("assets")).unwrap();
+        std::fs::write(ui_dir.join("index.html"), "<html></html>").unwrap();
+        std::fs::write(ui_dir.join("assets").join("main.js"), "").unwrap();
+        std::fs::write(data_dir.path().join("secret"), "secret").unwrap();
+        (data_dir, ui_dir)
+    }
+
+    #[test]
+    fn resolves_assets_inside_the_ui() {
+        let (_data_dir, ui_dir) = ui_dir();
+
+        let asset = sandboxed_path(&ui_dir, "assets/main.js").unwrap().unwrap();
+        assert!(asset.ends_with("assets/main.js"));
+        assert!(sandboxed_path(&ui_dir, "./index.html").unwrap().is_some());
+        assert!(sandboxed_path(&ui_dir, "missing.js").unwrap().is_none());
+        assert!(sandboxed_path(&ui_dir, "assets").unwrap().is_none());
+    }
+
+    #[test]
+    fn rejects_traversal_sequences() {
+        let (_data_dir, ui_dir) = ui_dir();
+
+        for asset_name in [
+            "../secret",
+            "../../secret",
+            "assets/../../secret",
+            "..\\secret",
+            "assets\\..\\..\\secret",
+        ] {
+            assert!(
+                matches!(
+                    sandboxed_path(&ui_dir, asset_name),
+                    Err(crate::Error::AssetOutsideOfUi(_))
+                ),
+                "{asset_name} was not rejected"
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_absolute_paths() {
+        let (data_dir, ui_dir) = ui_dir();
+        let secret = data_dir.path().join("secret");
+
+        assert!(matches!(
+            sandboxed_path(&ui_dir, secret.to_str().unwrap()),
+            Err(crate::Error::AssetOutsideOfUi(_))
+        ));
+        assert!(matches!(
+            sandboxed_path(&ui_dir, "/etc/passwd"),
+            Err(crate::Error::AssetOutsideOfUi(_))
+        ));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn rejects_symlinks_outside_of_the_ui() {
+        let (data_dir, ui_dir) = ui_dir();
+        std::os::unix::fs::symlink(data_dir.path().join("secret"), ui_dir.join("link")).unwrap();
+        std::os::unix::fs::symlink(data_dir.path(), ui_dir.join("assets").join("dir")).unwrap();
+        std::os::unix::fs::symlink(ui_dir.join("index.html"), ui_dir.join("inside")).unwrap();
+
+        assert!(matches!(
+            sandboxed_path(&ui_dir, "link"),
+            Err(crate::Error::AssetOutsideOfUi(_))
+        ));
+        assert!(matches!(
+            sandboxed_path(&ui_dir, "assets/dir/secret"),
+            Err(crate::Error::AssetOutsideOfUi(_))
+        ));
+        assert!(sandboxed_path(&ui_dir, "inside").unwrap().is_some());
+    }
+
+    #[test]
+    fn falls_back_to_index_html_only_for_navigations() {
+        let (_data_dir, ui_dir) = ui_dir();
+        let installed_ui = InstalledUi {
+            app_id: String::from("forum"),
+            ui_hash: String::from("ui-hash"),
+            content: UiContent::Directory(ui_dir.clone()),
+        };
+        let manifest = HappUiManifest::default();
+        let resolve = |asset_name: &str, spa_fallback: SpaFallback, is_navigation: bool| {
+            resolve_asset(
+                &installed_ui,
+                &manifest,
+                spa_fallback,
+                String::from(asset_name),
+                is_navigation,
+            )
+            .unwrap()
+        };
+
+        assert!(matches!(
+            resolve("posts/123", SpaFallback::NavigationOnly, true),
+            ResolvedAsset::Found(_)
+        ));
+        assert!(matches!(
+            resolve("assets/missing.js", SpaFallback::NavigationOnly, false),
+            ResolvedAsset::NotFound(None)
+        ));
+        assert!(matches!(
+            resolve("assets/missing.js", SpaFallback::Always, false),
+            ResolvedAsset::Found(_)
+        ));
+        assert!(matches!(
+            resolve("posts/123", SpaFallback::Disabled, true),
+            ResolvedAsset::NotFound(None)
+        ));
+
+        std::fs::write(ui_dir.join("404.html"), "Not found").unwrap();
+        assert!(matches!(
+            resolve("posts/123", SpaFallback::Disabled, true),
+            ResolvedAsset::NotFound(Some(_))
+        ));
     }
 }
@@ -1,18 +1,17 @@
 use std::{
-    collections::{HashMap, HashSet},
-    path::PathBuf,
+    collections::{BTreeMap, HashMap, HashSet},
     process,
+    sync::Arc,
     time::Duration,
 };
 
 use hc_seed_bundle::SharedLockedArray;
-use http_server::{pong_iframe, read_asset};
+use serde::Serialize;
 use tauri::{
-    async_runtime::RwLock,
-    http::response,
+    async_runtime::{Mutex, RwLock},
     ipc::CapabilityBuilder,
     plugin::{Builder, TauriPlugin},
-    AppHandle, Emitter, Manager, RunEvent, Runtime, WebviewUrl, WebviewWindowBuilder,
+    AppHandle, Emitter, Manager, RunEvent, Runtime, WebviewUrl, WebviewWindowBuilder, WindowEvent,
 };
 
 pub use holochain_client::*;
@@ -20,13 +19,25 @@ pub use holochain_types::prelude::*;
 pub use holochain_types::{web_app::WebAppBundle, websocket::AllowedOrigins};
 
 mod commands;
+mod config;
+#[cfg(feature = "deep-link")]
+mod deep_link;
 mod error;
+mod happ_ui_manifest;
 mod hc_live_file;
 mod http_server;
-
+mod network_status;
+mod router;
+mod security_headers;
+mod supervisor;
+
+pub use config::*;
+#[cfg(feature = "deep-link")]
+pub use deep_link::HappDeepLink;
 pub use error::{Error, Result};
 use hc_live_file::*;
 pub use holochain_runtime::*;
+use http_server::LocalhostUiServer;
 
 const ZOME_CALL_SIGNER_INITIALIZATION_SCRIPT: &'static str = include_str!("../zome-call-signer.js");
 
@@ -34,9 +45,85 @@ const ZOME_CALL_SIGNER_INITIALIZATION_SCRIPT: &'static str = include_str!("../zo
 pub struct HolochainPlugin<R: Runtime> {
     pub app_handle: AppHandle<R>,
     pub holochain_runtime: HolochainRuntime,
+    /// The name of the runtime, `DEFAULT_RUNTIME_NAME` for the one launched by `init()`, `async_init()` or `unlock()`
+    pub runtime_name: String,
+    /// The Content-Security-Policy for the UIs of the apps, from `HolochainPluginConfig::ui_content_security_policy`
+    pub ui_content_security_policy: UiContentSecurityPolicy,
+    /// How the UIs of the apps are served, from `HolochainPluginConfig::ui_serving_mode`,
+    /// with the port of `UiServingMode::LocalhostHttp` already picked
+    pub ui_serving_mode: UiServingMode,
+    /// When the UIs get their `index.html` for missing paths, from `HolochainPluginConfig::ui_spa_fallback`
+    pub ui_spa_fallback: SpaFallback,
+}
+
+impl<R: Runtime> Clone for HolochainPlugin<R> {
+    fn clone(&self) -> Self {
+        Self {
+            app_handle: self.app_handle.clone(),
+            holochain_runtime: self.holochain_runtime.clone(),
+            runtime_name: self.runtime_name.clone(),
+            ui_content_security_policy: self.ui_content_security_policy.clone(),
+            ui_serving_mode: self.ui_serving_mode,
+            ui_spa_fallback: self.ui_spa_fallback,
+        }
+    }
+}
+
+/// The name of the runtime launched by `init()`, `async_init()` or `unlock()`, accessed with `HolochainExt::holochain()`
+pub const DEFAULT_RUNTIME_NAME: &'static str = "default";
+
+/// The runtimes launched with `launch_named_runtime()`, by name
+struct NamedHolochainPlugins<R: Runtime>(std::sync::RwLock<BTreeMap<String, Arc<HolochainPlugin<R>>>>);
+
+/// A window built with `HolochainPlugin::web_happ_window_builder()` or `HolochainPlugin::main_window_builder()`
+#[derive(Clone, Debug)]
+pub(crate) struct HolochainWindow {
+    /// The runtime that built the window, to which its requests and commands are routed
+    pub(crate) runtime_name: String,
+    /// The app whose UI is open in the window, `None` for the main windows, which are trusted with all the apps
+    pub(crate) app_id: Option<InstalledAppId>,
 }
 
-fn happ_origin(app_id: &String) -> String {
+/// The windows built by the runtimes, by label
+/// Windows built by the app itself are not here, and belong to the default runtime
+#[derive(Default)]
+struct HolochainWindows(std::sync::RwLock<HashMap<String, HolochainWindow>>);
+
+/// Records the runtime and the app of the window with the given label
+fn register_holochain_window<R: Runtime>(
+    app_handle: &AppHandle<R>,
+    label: String,
+    window: HolochainWindow,
+) -> crate::Result<()> {
+    app_handle.manage(HolochainWindows::default());
+    app_handle
+        .state::<HolochainWindows>()
+        .0
+        .write()
+        .map_err(|err| crate::Error::LockError(format!("{err:?}")))?
+        .insert(label, window);
+    Ok(())
+}
+
+/// The runtime and the app of the window with the given label, `None` if it wasn't built by any runtime
+pub(crate) fn holochain_window<R: Runtime>(
+    app_handle: &AppHandle<R>,
+    label: &str,
+) -> Option<HolochainWindow> {
+    app_handle
+        .try_state::<HolochainWindows>()?
+        .0
+        .read()
+        .ok()?
+        .get(label)
+        .cloned()
+}
+
+fn happ_origin(ui_serving_mode: &UiServingMode, app_id: &String) -> String {
+    if let UiServingMode::LocalhostHttp { port: Some(port) } = ui_serving_mode {
+        // Browsers lowercase the hosts of http URLs, so the origin must be lowercase to match them
+        return format!("http://{}.localhost:{port}", app_id.to_lowercase());
+    }
     if cfg!(any(target_os = "windows", target_os = "android")) {
         format!("http://happ.{app_id}")
     } else {
@@ -53,6 +140,38 @@ fn main_window_origin() -> String {
 }
 
 impl<R: Runtime> HolochainPlugin<R> {
+    fn new(
+        app_handle: AppHandle<R>,
+        holochain_runtime: HolochainRuntime,
+        runtime_name: String,
+        config: &HolochainPluginConfig,
+        ui_server: &Option<LocalhostUiServer>,
+    ) -> Self {
+        let ui_serving_mode = match ui_server {
+            Some(ui_server) => ui_server.ui_serving_mode(),
+            None => config.ui_serving_mode,
+        };
+
+        Self {
+            app_handle,
+            holochain_runtime,
+            runtime_name,
+            ui_content_security_policy: config.ui_content_security_policy.clone(),
+            ui_serving_mode,
+            ui_spa_fallback: config.ui_spa_fallback,
+        }
+    }
+
+    /// The label of the window built with `Self::web_happ_window_builder()` for the given app
+    /// Windows of named runtimes are prefixed with the name of the runtime, so that each runtime can open the same app
+    pub fn web_happ_window_label(&self, app_id: &InstalledAppId) -> String {
+        if self.runtime_name.eq(DEFAULT_RUNTIME_NAME) {
+            app_id.clone()
+        } else {
+            format!("{}:{app_id}", self.runtime_name)
+        }
+    }
+
     /// Build a window that opens the UI for the given holochain web-app.
     ///
     /// * `app_id` - the app whose UI will be open. The must have been installed before with `Self::install_web_app()`.
@@ -76,15 +195,19 @@ impl<R: Runtime> HolochainPlugin<R> {
             .map(|n| n.to_string())
             .collect();
         let token = token_vector.join(",");
-        let url_origin = happ_origin(&app_id.clone().into());
+        let ui_serving_mode = self.ui_serving_mode;
+        let url_origin = happ_origin(&ui_serving_mode, &app_id);
 
         let url_path = url_path.unwrap_or_default();
 
-        let webview_url = tauri::WebviewUrl::CustomProtocol(url::Url::parse(
-            format!("{url_origin}/{url_path}").as_str(),
-        )?);
+        let url = url::Url::parse(format!("{url_origin}/{url_path}").as_str())?;
+        let webview_url = match ui_serving_mode {
+            UiServingMode::CustomProtocol => tauri::WebviewUrl::CustomProtocol(url),
+            UiServingMode::LocalhostHttp { .. } => tauri::WebviewUrl::External(url),
+        };
+        let label = self.web_happ_window_label(&app_id);
         let window_builder =
-            WebviewWindowBuilder::new(&self.app_handle, app_id.clone(), webview_url)
+            WebviewWindowBuilder::new(&self.app_handle, label.clone(), webview_url)
                 .initialization_script(
                     format!(
                         r#"
@@ -99,12 +222,25 @@ impl<R: Runtime> HolochainPlugin<R> {
                 )
                 .initialization_script(ZOME_CALL_SIGNER_INITIALIZATION_SCRIPT);
 
-        let mut capability_builder =
-            CapabilityBuilder::new("sign-zome-call").permission("holochain:allow-sign-zome-call");
+        let mut capability_builder = CapabilityBuilder::new("sign-zome-call")
+            .permission("holochain:allow-sign-zome-call")
+            .permission("holochain:allow-get-ui-asset-manifest");
 
-        capability_builder = capability_builder.window(app_id);
+        capability_builder = capability_builder.window(label.clone());
+        // Tauri only gives IPC access to remote URLs listed in the capability
+        if let UiServingMode::LocalhostHttp { .. } = ui_serving_mode {
+            capability_builder = capability_builder.remote(format!("{url_origin}/*"));
+        }
 
         self.app_handle.add_capability(capability_builder)?;
+        register_holochain_window(
+            &self.app_handle,
+            label,
+            HolochainWindow {
+                runtime_name: self.runtime_name.clone(),
+                app_id: Some(app_id),
+            },
+        )?;
 
         Ok(window_builder)
     }
@@ -131,6 +267,14 @@ impl<R: Runtime> HolochainPlugin<R> {
             // Pointing to index.html
             WebviewUrl::App(format!("{url_path}").into()),
         );
+        register_holochain_window(
+            &self.app_handle,
+            label.clone(),
+            HolochainWindow {
+                runtime_name: self.runtime_name.clone(),
+                app_id: None,
+            },
+        )?;
 
         if enable_admin_websocket {
             window_builder = window_builder.initialization_script(
@@ -208,7 +352,7 @@ impl<R: Runtime> HolochainPlugin<R> {
         if main_window {
             main_window_origin()
         } else {
-            happ_origin(&app_id)
+            happ_origin(&self.ui_serving_mode, &app_id)
         }
     }
 
@@ -325,6 +469,14 @@ impl<R: Runtime> HolochainPlugin<R> {
         Ok(())
     }
 
+    /// Uninstalls the given app from the holochain conductor, releasing the archive of its UI
+    ///
+    /// * `app_id` - the app to uninstall
+    pub async fn uninstall_app(&self, app_id: InstalledAppId) -> crate::Result<()> {
+        self.holochain_runtime.uninstall_app(app_id).await?;
+        Ok(())
+    }
+
     /// Checks whether it is necessary to update the hApp, and if so,
     /// updates the coordinator zomes for the given app with an updated `AppBundle`
     ///
@@ -369,6 +521,12 @@ impl<R: Runtime> HolochainPlugin<R> {
 // Extensions to [`tauri::App`], [`tauri::AppHandle`] and [`tauri::Window`] to access the holochain APIs.
 pub trait HolochainExt<R: Runtime> {
     fn holochain(&self) -> crate::Result<&HolochainPlugin<R>>;
+
+    fn named_holochain(&self, name: &str) -> crate::Result<Arc<HolochainPlugin<R>>>;
+
+    fn holochain_runtime_names(&self) -> Vec<String>;
+
+    fn window_holochain(&self, label: &str) -> crate::Result<Arc<HolochainPlugin<R>>>;
 }
 
 impl<R: Runtime, T: Manager<R>> crate::HolochainExt<R> for T {
@@ -380,9 +538,51 @@ impl<R: Runtime, T: Manager<R>> crate::HolochainExt<R> for T {
 
         Ok(s.inner())
     }
-}
 
-pub type HolochainPluginConfig = HolochainRuntimeConfig;
+    /// Access the runtime with the given name, launched with `launch_named_runtime()`
+    /// `DEFAULT_RUNTIME_NAME` gives access to the same runtime as `Self::holochain()`
+    fn named_holochain(&self, name: &str) -> crate::Result<Arc<HolochainPlugin<R>>> {
+        if name.eq(DEFAULT_RUNTIME_NAME) {
+            return Ok(Arc::new(self.holochain()?.clone()));
+        }
+
+        let named_plugins = self
+            .try_state::<NamedHolochainPlugins<R>>()
+            .ok_or(crate::Error::RuntimeNotFound(name.to_string()))?;
+        let named_plugins = named_plugins
+            .0
+            .read()
+            .map_err(|err| crate::Error::LockError(format!("{err:?}")))?;
+
+        named_plugins
+            .get(name)
+            .cloned()
+            .ok_or(crate::Error::RuntimeNotFound(name.to_string()))
+    }
+
+    /// The names of all the runtimes running in this Tauri app
+    fn holochain_runtime_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = Vec::new();
+        if self.try_state::<HolochainPlugin<R>>().is_some() {
+            names.push(DEFAULT_RUNTIME_NAME.to_string());
+        }
+        if let Some(named_plugins) = self.try_state::<NamedHolochainPlugins<R>>() {
+            if let Ok(named_plugins) = named_plugins.0.read() {
+                names.extend(named_plugins.keys().cloned());
+            }
+        }
+        names
+    }
+
+    /// Access the runtime of the window with the given label: the runtime that built it,
+    /// or the default runtime for the windows built by the app itself
+    fn window_holochain(&self, label: &str) -> crate::Result<Arc<HolochainPlugin<R>>> {
+        let runtime_name = holochain_window(self.app_handle(), label)
+            .map(|window| window.runtime_name)
+            .unwrap_or(DEFAULT_RUNTIME_NAME.to_string());
+        self.named_holochain(&runtime_name)
+    }
+}
 
 fn plugin_builder<R: Runtime>() -> Builder<R> {
     Builder::new("holochain")
@@ -392,100 +592,47 @@ fn plugin_builder<R: Runtime>() -> Builder<R> {
             commands::install::install_web_app,
             commands::install::uninstall_web_app,
             commands::install::list_apps,
-            commands::get_runtime_info::is_holochain_ready
+            commands::get_runtime_info::is_holochain_ready,
+            commands::passphrase::unlock,
+            commands::passphrase::verify_passphrase,
+            commands::passphrase::change_passphrase,
+            commands::device_seed::export_device_seed_backup,
+            commands::device_seed::unlock_and_restore_device_seed_backup,
+            commands::backup::create_backup,
+            commands::backup::restore_backup,
+            commands::agent_keys::generate_agent_key,
+            commands::agent_keys::list_agent_keys,
+            commands::agent_keys::set_agent_key_label,
+            commands::mdns::get_mdns_discovery_settings,
+            commands::mdns::set_mdns_discovery_enabled,
+            commands::mdns::set_mdns_discovery_participants,
+            commands::network_status::network_status,
+            commands::network_profile::get_network_profile,
+            commands::network_profile::set_network_profile,
+            commands::peer_info::export_peer_info,
+            commands::peer_info::import_peer_info,
+            commands::ui::get_ui_asset_manifest
         ])
-        .register_uri_scheme_protocol("happ", |context, request| {
-            log::info!("Received request {}", request.uri().to_string());
-            if request.uri().to_string().starts_with("happ://ping") {
-                return response::Builder::new()
-                    .status(tauri::http::StatusCode::ACCEPTED)
-                    .header("Content-Type", "text/html;charset=utf-8")
-                    .body(pong_iframe().as_bytes().to_vec())
-                    .expect("Failed to build body of accepted response");
-            }
-            // prepare our response
-            tauri::async_runtime::block_on(async move {
-                // let mutex = app_handle.state::<Mutex<AdminWebsocket>>();
-                // let mut admin_ws = mutex.lock().await;
-
-                let uri_without_protocol = request
-                    .uri()
-                    .to_string()
-                    .split("://")
-                    .map(|s| s.to_string())
-                    .collect::<Vec<String>>()
-                    .get(1)
-                    .expect("Malformed request: not enough items")
-                    .clone();
-                let uri_without_querystring: String = uri_without_protocol
-                    .split("?")
-                    .map(|s| s.to_string())
-                    .collect::<Vec<String>>()
-                    .get(0)
-                    .expect("Malformed request: not enough items 2")
-                    .clone();
-                let uri_components: Vec<String> = uri_without_querystring
-                    .split("/")
-                    .map(|s| s.to_string())
-                    .collect();
-                let lowercase_app_id = uri_components
-                    .get(0)
-                    .expect("Malformed request: not enough items 3");
-                let mut asset_file = PathBuf::new();
-                for i in 1..uri_components.len() {
-                    asset_file = asset_file.join(uri_components[i].clone());
-                }
-
-                let Ok(holochain_plugin) = context.app_handle().holochain() else {
-                    return response::Builder::new()
-                        .status(tauri::http::StatusCode::INTERNAL_SERVER_ERROR)
-                        .body(
-                            format!("Called http UI before initializing holochain")
-                                .as_bytes()
-                                .to_vec(),
-                        )
-                        .expect("Failed to build asset with not internal server error");
-                };
-
-                let r = match read_asset(
-                    &holochain_plugin.holochain_runtime.filesystem,
-                    lowercase_app_id,
-                    asset_file
-                        .as_os_str()
-                        .to_str()
-                        .expect("Malformed request: not enough items 4")
-                        .to_string(),
-                )
-                .await
-                {
-                    Ok(Some((asset, mime_type))) => {
-                        log::info!("Got asset for app with id: {}", lowercase_app_id);
-                        let mut response =
-                            response::Builder::new().status(tauri::http::StatusCode::ACCEPTED);
-                        if let Some(mime_type) = mime_type {
-                            response = response
-                                .header("Content-Type", format!("{};charset=utf-8", mime_type))
-                        } else {
-                            response = response.header("Content-Type", "charset=utf-8")
-                        }
-
-                        return response
-                            .body(asset)
-                            .expect("Failed to build response with asset");
-                    }
-                    Ok(None) => response::Builder::new()
-                        .status(tauri::http::StatusCode::NOT_FOUND)
-                        .body(vec![])
-                        .expect("Failed to build asset with not found"),
-                    Err(e) => response::Builder::new()
-                        .status(500)
-                        .body(format!("{:?}", e).into())
-                        .expect("Failed to build body of error response"),
-                };
-                r
-            })
+        .register_asynchronous_uri_scheme_protocol("happ", |context, request, responder| {
+            let app_handle = context.app_handle().clone();
+            let webview_label = context.webview_label().to_string();
+            tauri::async_runtime::spawn(async move {
+                let holochain = app_handle.window_holochain(&webview_label);
+                responder.respond(router::handle_protocol_request(holochain, request).await);
+            });
         })
         .on_event(|app, event| match event {
+            RunEvent::WindowEvent {
+                label,
+                event: WindowEvent::Destroyed,
+                ..
+            } => {
+                if let Some(windows) = app.try_state::<HolochainWindows>() {
+                    if let Ok(mut windows) = windows.0.write() {
+                        windows.remove(label);
+                    }
+                }
+            }
             RunEvent::Exit => {
                 if tauri::is_dev() {
                     if let Ok(h) = app.holochain() {
@@ -498,8 +645,8 @@ fn plugin_builder<R: Runtime>() -> Builder<R> {
             RunEvent::ExitRequested { code, api, .. } => {
                 api.prevent_exit();
 
-                if let Err(err) = shutdown_runtime(app) {
-                    log::error!("Error shutting down holochain runtime: {err:?}.");
+                if let Err(err) = shutdown_runtimes(app) {
+                    log::error!("Error shutting down holochain runtimes: {err:?}.");
 
                     process::exit(1);
                 } else {
@@ -510,20 +657,58 @@ fn plugin_builder<R: Runtime>() -> Builder<R> {
         })
 }
 
-fn shutdown_runtime<R: Runtime>(app: &AppHandle<R>) -> crate::Result<()> {
-    let result: std::result::Result<crate::Result<()>, tokio::time::error::Elapsed> = tokio_helper::block_on(
-        async move {
-            let holochain = app
-                .holochain()
-                .map_err(|_err| crate::Error::HolochainNotInitializedError)?;
+/// Payload of the `holochain://shutdown-progress` event
+#[derive(Serialize, Clone, Debug)]
+struct ShutdownProgress {
+    runtime: String,
+    phase: ShutdownPhase,
+}
+
+/// All the runtimes running in this Tauri app, by name
+fn running_plugins<R: Runtime>(app: &AppHandle<R>) -> Vec<(String, Arc<HolochainPlugin<R>>)> {
+    app.holochain_runtime_names()
+        .into_iter()
+        .filter_map(|name| {
+            let plugin = app.named_holochain(&name).ok()?;
+            Some((name, plugin))
+        })
+        .collect()
+}
 
-            holochain.holochain_runtime.shutdown().await?;
+/// Shuts down all the runtimes in parallel, emitting `holochain://shutdown-progress` as each of their phases starts
+/// The runtimes give up by themselves after their shutdown timeout, this only blocks for a little longer as a last resort
+fn shutdown_runtimes<R: Runtime>(app: &AppHandle<R>) -> crate::Result<()> {
+    let plugins = running_plugins(app);
+    let timeout = plugins
+        .iter()
+        .map(|(_, plugin)| plugin.holochain_runtime.shutdown_timeout())
+        .max()
+        .unwrap_or_default()
+        + Duration::from_secs(1);
+
+    let result: std::result::Result<Vec<crate::Result<()>>, tokio::time::error::Elapsed> = tokio_helper::block_on(
+        futures::future::join_all(plugins.iter().map(async |(name, plugin)| {
+            plugin
+                .holochain_runtime
+                .shutdown_with_progress(|phase| {
+                    let progress = ShutdownProgress {
+                        runtime: name.clone(),
+                        phase,
+                    };
+                    if let Err(err) = app.emit("holochain://shutdown-progress", progress) {
+                        log::error!("Failed to emit shutdown progress: {err:?}");
+                    }
+                })
+                .await?;
 
             Ok(())
-        },
-        Duration::from_secs(3),
+        })),
+        timeout,
     );
-    result.map_err(|err| crate::Error::ShutdownError(format!("{err:?}")))??;
+    let results = result.map_err(|err| crate::Error::ShutdownError(format!("{err:?}")))?;
+    for result in results {
+        result?;
+    }
     Ok(())
 }
 
@@ -569,28 +754,125 @@ pub fn async_init<R: Runtime>(
         .build()
 }
 
-static RUNNING_HOLOCHAIN_RUNTIME: RwLock<Option<HolochainRuntime>> = RwLock::const_new(None);
+/// Holds the config for the plugin until holochain is unlocked
+pub(crate) struct LockedHolochain {
+    pub(crate) config: HolochainPluginConfig,
+    pub(crate) unlocking: Mutex<()>,
+}
+
+/// Initializes the plugin without launching holochain
+/// Holochain stays locked until `unlock()` or the `unlock` command are called with the passphrase for the keystore,
+/// so that the app can prompt the user for it first
+/// If you use this version of init, you should listen to the `holochain://setup-completed` event in your `setup()` hook
+pub fn locked_init<R: Runtime>(config: HolochainPluginConfig) -> TauriPlugin<R> {
+    plugin_builder()
+        .setup(|app, _api| {
+            app.manage(LockedHolochain {
+                config,
+                unlocking: Mutex::new(()),
+            });
+
+            Ok(())
+        })
+        .build()
+}
+
+/// Launches holochain for a plugin initialized with `locked_init()`
+/// Fails with `holochain_runtime::Error::WrongPassphrase` if the passphrase doesn't unlock the keystore,
+/// in which case this can be called again with another passphrase
+pub async fn unlock<R: Runtime>(
+    app_handle: AppHandle<R>,
+    passphrase: SharedLockedArray,
+) -> crate::Result<()> {
+    unlock_with_config(app_handle, passphrase, |config| config).await
+}
+
+/// Launches holochain for a plugin initialized with `locked_init()`, restoring the device seed from the given backup
+/// This is meant for fresh installs, see `HolochainRuntimeConfig::restore_device_seed_backup()`
+pub async fn unlock_and_restore_device_seed_backup<R: Runtime>(
+    app_handle: AppHandle<R>,
+    passphrase: SharedLockedArray,
+    device_seed_backup: Vec<u8>,
+    device_seed_backup_passphrase: SharedLockedArray,
+) -> crate::Result<()> {
+    unlock_with_config(app_handle, passphrase, |config| {
+        config.restore_device_seed_backup(device_seed_backup, device_seed_backup_passphrase)
+    })
+    .await
+}
 
+async fn unlock_with_config<R: Runtime>(
+    app_handle: AppHandle<R>,
+    passphrase: SharedLockedArray,
+    map_config: impl FnOnce(HolochainPluginConfig) -> HolochainPluginConfig,
+) -> crate::Result<()> {
+    let locked_holochain = app_handle
+        .try_state::<LockedHolochain>()
+        .ok_or(crate::Error::HolochainNotLockedError)?;
+    let _unlocking = locked_holochain.unlocking.lock().await;
+
+    if app_handle.try_state::<HolochainPlugin<R>>().is_some() {
+        return Ok(());
+    }
+
+    launch_and_setup_holochain(
+        app_handle.clone(),
+        passphrase,
+        map_config(locked_holochain.config.clone()),
+    )
+    .await
+}
+
+/// The runtimes running in this process, by name
+static RUNNING_HOLOCHAIN_RUNTIMES: RwLock<BTreeMap<String, HolochainRuntime>> =
+    RwLock::const_new(BTreeMap::new());
+
+/// Launches the default holochain runtime for this process, see `launch_named_holochain_runtime()`
 pub async fn launch_holochain_runtime(
     passphrase: SharedLockedArray,
-    config: HolochainPluginConfig,
+    config: HolochainRuntimeConfig,
 ) -> crate::Result<HolochainRuntime> {
-    log::debug!("Attempting to lock process wide holochain runtime RwLock.");
-    let mut lock = RUNNING_HOLOCHAIN_RUNTIME.write().await;
-    log::debug!("Successfully locked process wide holochain runtime RwLock.");
+    launch_named_holochain_runtime(DEFAULT_RUNTIME_NAME.to_string(), passphrase, config).await
+}
 
-    if let Some(runtime) = lock.to_owned() {
+/// Launches a holochain runtime with the given name, independent from the other runtimes in this process
+/// If a runtime with this name is already running in the same `holochain_dir`, returns that one instead
+/// Fails if the name or the `holochain_dir` are already used by another runtime
+///
+/// * `name` - the name that identifies the runtime in this process
+pub async fn launch_named_holochain_runtime(
+    name: String,
+    passphrase: SharedLockedArray,
+    config: HolochainRuntimeConfig,
+) -> crate::Result<HolochainRuntime> {
+    log::debug!("Attempting to lock process wide holochain runtimes RwLock.");
+    let mut lock = RUNNING_HOLOCHAIN_RUNTIMES.write().await;
+    log::debug!("Successfully locked process wide holochain runtimes RwLock.");
+
+    if let Some(runtime) = lock.get(&name) {
+        if runtime.filesystem.app_data_dir.ne(&config.holochain_dir) {
+            return Err(crate::Error::RuntimeNameAlreadyInUse(name));
+        }
         log::info!(
-            "There was already a holochain runtime running for this process, returning that."
+            "There was already a holochain runtime named {name} running for this process, returning that."
         );
-        return Ok(runtime);
+        return Ok(runtime.clone());
     }
-    log::info!("There was no holochain runtime running in this process yet. Launching...");
+    if let Some((other_name, _)) = lock
+        .iter()
+        .find(|(_, runtime)| runtime.filesystem.app_data_dir.eq(&config.holochain_dir))
+    {
+        return Err(crate::Error::HolochainDirAlreadyInUse(
+            other_name.clone(),
+            config.holochain_dir,
+        ));
+    }
+    log::info!("There was no holochain runtime named {name} running in this process yet. Launching...");
 
     let crypto_provider = rustls::crypto::aws_lc_rs::default_provider().install_default();
     if crypto_provider.is_err() {
-        log::error!(
-            "could not set crypto provider for tls: {:?}.",
+        log::debug!(
+            "could not set crypto provider for tls, it may have been installed by another runtime: {:?}.",
             crypto_provider
         );
     }
@@ -599,19 +881,103 @@ pub async fn launch_holochain_runtime(
 
     let holochain_runtime = HolochainRuntime::launch(passphrase, config).await?;
 
-    log::info!("Successfully launched holochain runtime.");
+    log::info!("Successfully launched holochain runtime {name}.");
 
-    *lock = Some(holochain_runtime.clone());
+    lock.insert(name, holochain_runtime.clone());
 
     Ok(holochain_runtime)
 }
 
+/// Launches another holochain runtime in this Tauri app, with its own directory, keystore and network,
+/// to be accessed with `HolochainExt::named_holochain()`
+/// Useful to isolate profiles, or to run multiple agents in a single binary
+/// All runtimes are shut down when the app exits
+///
+/// * `name` - the name to access the runtime with, which can't be `DEFAULT_RUNTIME_NAME`
+/// * `passphrase` - the passphrase for the keystore of this runtime
+/// * `config` - the config for this runtime, which must have a `holochain_dir` not used by any other runtime
+pub async fn launch_named_runtime<R: Runtime>(
+    app_handle: AppHandle<R>,
+    name: String,
+    passphrase: SharedLockedArray,
+    config: HolochainPluginConfig,
+) -> crate::Result<Arc<HolochainPlugin<R>>> {
+    check_runtime_name(&name)?;
+
+    if let Ok(plugin) = app_handle.named_holochain(&name) {
+        // Fails if the runtime is running in another directory
+        launch_named_holochain_runtime(name, passphrase, config.holochain_runtime_config).await?;
+        return Ok(plugin);
+    }
+
+    let ui_server = bind_ui_server(&config).await?;
+    let holochain_runtime = launch_named_holochain_runtime(
+        name.clone(),
+        passphrase,
+        config.holochain_runtime_config.clone(),
+    )
+    .await?;
+
+    app_handle.manage(NamedHolochainPlugins::<R>(std::sync::RwLock::new(
+        BTreeMap::new(),
+    )));
+    let named_plugins = app_handle.state::<NamedHolochainPlugins<R>>();
+    let mut named_plugins = named_plugins
+        .0
+        .write()
+        .map_err(|err| crate::Error::LockError(format!("{err:?}")))?;
+
+    let plugin = named_plugins
+        .entry(name.clone())
+        .or_insert_with(|| {
+            let plugin = Arc::new(HolochainPlugin::new(
+                app_handle.clone(),
+                holochain_runtime,
+                name,
+                &config,
+                &ui_server,
+            ));
+            network_status::spawn_network_status_watcher(&plugin);
+            supervisor::spawn_supervisor(&plugin);
+            if let Some(ui_server) = ui_server {
+                ui_server.serve(plugin.clone());
+            }
+            plugin
+        })
+        .clone();
+
+    Ok(plugin)
+}
+
+/// Fails with `Error::ReservedRuntimeName` for the name of the default runtime, which can't be launched by name
+fn check_runtime_name(name: &String) -> crate::Result<()> {
+    if name.eq(DEFAULT_RUNTIME_NAME) {
+        return Err(crate::Error::ReservedRuntimeName(name.clone()));
+    }
+    Ok(())
+}
+
+/// Binds the server for the UIs of the apps if the config uses `UiServingMode::LocalhostHttp`
+/// Done before launching the runtime, so that the launch fails if the port is not available
+async fn bind_ui_server(
+    config: &HolochainPluginConfig,
+) -> crate::Result<Option<LocalhostUiServer>> {
+    let UiServingMode::LocalhostHttp { port } = config.ui_serving_mode else {
+        return Ok(None);
+    };
+    Ok(Some(LocalhostUiServer::bind(port).await?))
+}
+
 async fn launch_and_setup_holochain<R: Runtime>(
     app_handle: AppHandle<R>,
     passphrase: SharedLockedArray,
     config: HolochainPluginConfig,
 ) -> crate::Result<()> {
-    let holochain_runtime = launch_holochain_runtime(passphrase, config).await?;
+    #[cfg(feature = "deep-link")]
+    let deep_link_scheme = config.deep_link_scheme.clone();
+    let ui_server = bind_ui_server(&config).await?;
+    let holochain_runtime =
+        launch_holochain_runtime(passphrase, config.holochain_runtime_config.clone()).await?;
 
     #[cfg(desktop)]
     if tauri::is_dev() {
@@ -630,21 +996,51 @@ async fn launch_and_setup_holochain<R: Runtime>(
                 log::error!("Failed to delete hc live file: {err:?}");
             }
         }
-        if let Err(err) = shutdown_runtime(&h) {
+        if let Err(err) = shutdown_runtimes(&h) {
             log::error!("Failed to shutdown holochain runtime: {err:?}");
         }
         std::process::exit(0);
     });
 
-    let p = HolochainPlugin::<R> {
-        app_handle: app_handle.clone(),
+    let p = HolochainPlugin::<R>::new(
+        app_handle.clone(),
         holochain_runtime,
-    };
+        DEFAULT_RUNTIME_NAME.to_string(),
+        &config,
+        &ui_server,
+    );
 
     // manage state so it is accessible by the commands
     app_handle.manage(p);
 
+    network_status::spawn_network_status_watcher(app_handle.holochain()?);
+    supervisor::spawn_supervisor(app_handle.holochain()?);
+    if let Some(ui_server) = ui_server {
+        ui_server.serve(Arc::new(app_handle.holochain()?.clone()));
+    }
+
+    #[cfg(feature = "deep-link")]
+    if let Some(scheme) = deep_link_scheme {
+        if let Err(err) = deep_link::setup_deep_links(&app_handle, scheme) {
+            log::error!("Failed to set up deep links: {err:?}");
+        }
+    }
+
     app_handle.emit("holochain://setup-completed", ())?;
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejects_the_name_of_the_default_runtime() {
+        assert!(matches!(
+            check_runtime_name(&DEFAULT_RUNTIME_NAME.to_string()),
+            Err(crate::Error::ReservedRuntimeName(_))
+        ));
+        assert!(check_runtime_name(&String::from("profile-1")).is_ok());
+    }
+}
@@ -0,0 +1,92 @@
+use std::{collections::BTreeSet, time::Duration};
+
+use holochain_runtime::{HolochainRuntime, NetworkStatus};
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Runtime};
+
+use crate::HolochainPlugin;
+
+/// Interval between checks for changes in the network status
+const NETWORK_STATUS_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Payload of the `holochain://network-status` event
+#[derive(Serialize, Clone, Debug)]
+struct RuntimeNetworkStatus {
+    runtime: String,
+    #[serde(flatten)]
+    network_status: NetworkStatus,
+}
+
+/// The remote peers for each cell of each app, with whether they are online
+fn peer_set(network_status: &NetworkStatus) -> BTreeSet<(String, String, String, String, bool)> {
+    let mut peers = BTreeSet::new();
+    for app in &network_status.apps {
+        for (role_name, cells) in &app.roles {
+            for cell in cells {
+                for peer in &cell.peers {
+                    peers.insert((
+                        app.installed_app_id.clone(),
+                        role_name.clone(),
+                        cell.dna_hash.to_string(),
+                        peer.agent.clone(),
+                        peer.url.is_some(),
+                    ));
+                }
+            }
+        }
+    }
+    peers
+}
+
+/// Whether the peers or the reachability of the servers changed
+/// The agent infos of the peers are refreshed periodically, so their expiry is not compared
+fn has_changed(previous: &Option<NetworkStatus>, current: &NetworkStatus) -> bool {
+    let Some(previous) = previous else {
+        return true;
+    };
+    previous.bootstrap_reachable != current.bootstrap_reachable
+        || previous.signal_reachable != current.signal_reachable
+        || peer_set(previous).ne(&peer_set(current))
+}
+
+/// Emits the `holochain://network-status` event with the new `NetworkStatus` of the given runtime every time it changes,
+/// along with the name of the runtime, until the runtime shuts down
+pub(crate) fn spawn_network_status_watcher<R: Runtime>(holochain: &HolochainPlugin<R>) {
+    let app_handle = holochain.app_handle.clone();
+    let runtime_name = holochain.runtime_name.clone();
+    let holochain_runtime = holochain.holochain_runtime.clone();
+    tauri::async_runtime::spawn(async move {
+        tokio::select! {
+            _ = watch_network_status(app_handle, runtime_name, &holochain_runtime) => {}
+            _ = holochain_runtime.shutdown_started() => {}
+        }
+    });
+}
+
+async fn watch_network_status<R: Runtime>(
+    app_handle: AppHandle<R>,
+    runtime_name: String,
+    holochain_runtime: &HolochainRuntime,
+) {
+    let mut last_network_status: Option<NetworkStatus> = None;
+    loop {
+        match holochain_runtime.network_status().await {
+            Ok(network_status) => {
+                if has_changed(&last_network_status, &network_status) {
+                    let payload = RuntimeNetworkStatus {
+                        runtime: runtime_name.clone(),
+                        network_status: network_status.clone(),
+                    };
+                    if let Err(err) = app_handle.emit("holochain://network-status", payload) {
+                        log::error!("Failed to emit \"holochain://network-status\" event: {err:?}");
+                    }
+                    last_network_status = Some(network_status);
+                }
+            }
+            Err(err) => {
+                log::error!("Failed to get the network status: {err:?}");
+            }
+        }
+        tokio::time::sleep(NETWORK_STATUS_INTERVAL).await;
+    }
+}
@@ -0,0 +1,369 @@
+use std::sync::Arc;
+
+use holochain_runtime::FileSystem;
+use percent_encoding::percent_decode_str;
+use tauri::{
+    http::{header, response, Request, Response, StatusCode, Uri},
+    Runtime,
+};
+use url::Url;
+
+use crate::{
+    happ_ui_manifest::{read_happ_ui_manifest, HappUiManifest, HAPP_UI_MANIFEST_FILE},
+    http_server::{
+        asset_response, not_found_page_response, pong_iframe, resolve_asset, ResolvedAsset,
+        ResponseBody,
+    },
+    security_headers::add_security_headers,
+    HolochainPlugin, SpaFallback,
+};
+
+/// The host prefix of the origins for `happ` URLs on Windows and Android, where they are served as `http://happ.{app_id}`
+const HAPP_HOST_PREFIX: &'static str = "happ.";
+
+/// The host suffix of the origins for `happ` URLs served with `UiServingMode::LocalhostHttp`, as `http://{app_id}.localhost:{port}`
+const LOCALHOST_HOST_SUFFIX: &'static str = ".localhost";
+
+/// The app id the webview uses to check whether the `happ` protocol is available
+const PING_APP_ID: &'static str = "ping";
+
+/// What a request to the `happ` protocol is asking for
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum HappRoute {
+    /// Check whether the protocol is available
+    Ping,
+    /// An asset of the UI for an app
+    Asset {
+        /// The app id as it comes in the host of the URL, which the webview may have lowercased
+        lowercase_app_id: String,
+        /// The percent-decoded path of the asset, without the leading slash
+        path: String,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum RouteError {
+    BadRequest(String),
+}
+
+/// Parses the URL of a request to the `happ` protocol, in any of the forms produced by `happ_origin()`:
+/// `happ://{app_id}/{path}`, `http://happ.{app_id}/{path}` or `http://{app_id}.localhost:{port}/{path}`
+/// The query string and the fragment are ignored
+pub(crate) fn route(uri: &Uri) -> Result<HappRoute, RouteError> {
+    let url = Url::parse(uri.to_string().as_str())
+        .map_err(|err| RouteError::BadRequest(format!("Invalid URL {uri}: {err}")))?;
+
+    let host = url
+        .host_str()
+        .ok_or(RouteError::BadRequest(format!("URL {uri} has no host")))?;
+
+    let app_id = match url.scheme() {
+        "happ" => host,
+        "http" if host.ends_with(LOCALHOST_HOST_SUFFIX) => {
+            &host[..host.len() - LOCALHOST_HOST_SUFFIX.len()]
+        }
+        "http" | "https" => host
+            .strip_prefix(HAPP_HOST_PREFIX)
+            .ok_or(RouteError::BadRequest(format!(
+                "Host {host} is not a happ origin"
+            )))?,
+        scheme => {
+            return Err(RouteError::BadRequest(format!(
+                "Unsupported scheme {scheme}"
+            )))
+        }
+    };
+
+    if app_id.is_empty() {
+        return Err(RouteError::BadRequest(format!("URL {uri} has no app id")));
+    }
+    if app_id.eq(PING_APP_ID) {
+        return Ok(HappRoute::Ping);
+    }
+
+    let mut segments: Vec<String> = Vec::new();
+    for segment in url.path().split('/').filter(|segment| !segment.is_empty()) {
+        let segment = percent_decode_str(segment)
+            .decode_utf8()
+            .map_err(|err| RouteError::BadRequest(format!("Invalid path {}: {err}", url.path())))?;
+
+        // Encoded separators would otherwise change how the path is split
+        if segment.contains(['/', '\\', '\0']) {
+            return Err(RouteError::BadRequest(format!(
+                "Invalid path segment {segment}"
+            )));
+        }
+        segments.push(segment.to_string());
+    }
+
+    Ok(HappRoute::Asset {
+        lowercase_app_id: app_id.to_string(),
+        path: segments.join("/"),
+    })
+}
+
+fn text_response(status: StatusCode, body: String) -> Response<Vec<u8>> {
+    response::Builder::new()
+        .status(status)
+        .header("Content-Type", "text/plain;charset=utf-8")
+        .body(body.into_bytes())
+        .expect("Failed to build text response")
+}
+
+fn error_response(lowercase_app_id: &String, error: crate::Error) -> Response<Vec<u8>> {
+    match error {
+        crate::Error::HolochainRuntimeError(
+            holochain_runtime::Error::AppDoesNotExist(_)
+            | holochain_runtime::Error::AppDoesNotHaveUIError(_),
+        ) => text_response(
+            StatusCode::NOT_FOUND,
+            format!("App {lowercase_app_id} does not have a UI"),
+        ),
+        crate::Error::AssetOutsideOfUi(asset_name) => {
+            log::warn!("Rejected request for asset outside of the UI: {asset_name}");
+            text_response(StatusCode::FORBIDDEN, String::from("Forbidden"))
+        }
+        e => text_response(StatusCode::INTERNAL_SERVER_ERROR, format!("{:?}", e)),
+    }
+}
+
+/// Whether the request is a navigation, as opposed to a request for a script, a style or any other subresource
+fn is_navigation_request(request: &Request<Vec<u8>>, path: &str) -> bool {
+    let header = |name: header::HeaderName| {
+        request
+            .headers()
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+    };
+    let has_extension = path
+        .rsplit('/')
+        .next()
+        .map(|file_name| file_name.contains('.'))
+        .unwrap_or(false);
+
+    header(header::HeaderName::from_static("sec-fetch-mode")).eq(&Some("navigate"))
+        || header(header::ACCEPT)
+            .map(|accept| accept.contains("text/html"))
+            .unwrap_or(false)
+        || !has_extension
+}
+
+/// Resolves and reads the requested asset, answering `404 Not Found` if the app or the asset don't exist
+/// Returns the `happ-ui.json` of the UI along with the response, or the response to return as is if there was an error
+fn serve_asset(
+    filesystem: &FileSystem,
+    spa_fallback: SpaFallback,
+    request: &Request<Vec<u8>>,
+    lowercase_app_id: String,
+    path: String,
+) -> Result<(Response<ResponseBody>, HappUiManifest), Response<Vec<u8>>> {
+    let installed_ui = filesystem
+        .bundle_store
+        .get_ui(&lowercase_app_id)
+        .map_err(|err| error_response(&lowercase_app_id, err.into()))?;
+
+    let manifest = match read_happ_ui_manifest(&installed_ui.content) {
+        Ok(manifest) => manifest,
+        Err(err) => {
+            log::error!(
+                "Failed to read {HAPP_UI_MANIFEST_FILE} for app {lowercase_app_id}: {err:?}"
+            );
+            HappUiManifest::default()
+        }
+    };
+    let spa_fallback = manifest.spa_fallback.unwrap_or(spa_fallback);
+    let is_navigation = is_navigation_request(request, &path);
+
+    let response = match resolve_asset(&installed_ui, &manifest, spa_fallback, path, is_navigation)
+    {
+        Ok(ResolvedAsset::Found(asset)) => asset_response(request, asset),
+        Ok(ResolvedAsset::NotFound(Some(page))) => {
+            not_found_page_response(page).map(|response| response.map(ResponseBody::from))
+        }
+        Ok(ResolvedAsset::NotFound(None)) => {
+            return Err(text_response(
+                StatusCode::NOT_FOUND,
+                String::from("Not found"),
+            ))
+        }
+        Err(err) => return Err(error_response(&lowercase_app_id, err)),
+    };
+
+    match response {
+        Ok(response) => Ok((response, manifest)),
+        Err(err) => Err(error_response(&lowercase_app_id, err)),
+    }
+}
+
+/// Handles a request to the `happ` custom protocol, which can't stream its responses,
+/// so the whole body of the assets is read before responding
+///
+/// * `holochain` - the runtime whose apps to serve, or the error if it hasn't been launched yet
+pub(crate) async fn handle_protocol_request<R: Runtime>(
+    holochain: crate::Result<Arc<HolochainPlugin<R>>>,
+    request: Request<Vec<u8>>,
+) -> Response<Vec<u8>> {
+    let (parts, body) = handle_request(holochain, request).await.into_parts();
+    match tauri::async_runtime::spawn_blocking(move || body.into_bytes()).await {
+        Ok(Ok(bytes)) => Response::from_parts(parts, bytes),
+        Ok(Err(e)) => text_response(StatusCode::INTERNAL_SERVER_ERROR, format!("{:?}", e)),
+        Err(e) => text_response(StatusCode::INTERNAL_SERVER_ERROR, format!("{:?}", e)),
+    }
+}
+
+/// Handles a request to the `happ` protocol, serving the UI assets of the installed apps
+/// Shared by the custom protocol and the server for `UiServingMode::LocalhostHttp`, which streams the bodies of the assets
+///
+/// * `holochain` - the runtime whose apps to serve, or the error if it hasn't been launched yet
+pub(crate) async fn handle_request<R: Runtime>(
+    holochain: crate::Result<Arc<HolochainPlugin<R>>>,
+    request: Request<Vec<u8>>,
+) -> Response<ResponseBody> {
+    log::info!("Received request {}", request.uri());
+
+    let (lowercase_app_id, path) = match route(request.uri()) {
+        Ok(HappRoute::Ping) => {
+            return response::Builder::new()
+                .status(StatusCode::OK)
+                .header("Content-Type", "text/html;charset=utf-8")
+                .body(pong_iframe().as_bytes().to_vec().into())
+                .expect("Failed to build body of accepted response");
+        }
+        Ok(HappRoute::Asset {
+            lowercase_app_id,
+            path,
+        }) => (lowercase_app_id, path),
+        Err(RouteError::BadRequest(reason)) => {
+            log::warn!("Bad request to the happ protocol: {reason}");
+            return text_response(StatusCode::BAD_REQUEST, reason).map(ResponseBody::from);
+        }
+    };
+
+    let Ok(holochain) = holochain else {
+        return text_response(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            String::from("Called http UI before initializing holochain"),
+        )
+        .map(ResponseBody::from);
+    };
+
+    let filesystem = holochain.holochain_runtime.filesystem.clone();
+    let spa_fallback = holochain.ui_spa_fallback;
+    let asset_app_id = lowercase_app_id.clone();
+    // Resolving and reading the asset hits the filesystem, so it's done in the blocking thread pool,
+    // letting the webview keep loading the other assets of the page concurrently
+    let (mut response, manifest) = match tauri::async_runtime::spawn_blocking(move || {
+        serve_asset(&filesystem, spa_fallback, &request, asset_app_id, path)
+    })
+    .await
+    {
+        Ok(Ok(served)) => served,
+        Ok(Err(response)) => return response.map(ResponseBody::from),
+        Err(e) => {
+            return text_response(StatusCode::INTERNAL_SERVER_ERROR, format!("{:?}", e))
+                .map(ResponseBody::from)
+        }
+    };
+
+    add_security_headers(
+        &holochain,
+        &lowercase_app_id,
+        manifest.content_security_policy,
+        &mut response,
+    )
+    .await;
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn route_str(uri: &str) -> Result<HappRoute, RouteError> {
+        route(&uri.parse::<Uri>().unwrap())
+    }
+
+    fn asset(app_id: &str, path: &str) -> Result<HappRoute, RouteError> {
+        Ok(HappRoute::Asset {
+            lowercase_app_id: app_id.to_string(),
+            path: path.to_string(),
+        })
+    }
+
+    #[test]
+    fn routes_both_origin_forms() {
+        assert_eq!(route_str("happ://ping"), Ok(HappRoute::Ping));
+        assert_eq!(route_str("http://happ.ping/"), Ok(HappRoute::Ping));
+        assert_eq!(route_str("happ://forum/"), asset("forum", ""));
+        assert_eq!(
+            route_str("happ://forum/assets/main.js?v=1#top"),
+            asset("forum", "assets/main.js")
+        );
+        assert_eq!(
+            route_str("http://happ.forum/assets/main.js"),
+            asset("forum", "assets/main.js")
+        );
+        assert_eq!(
+            route_str("happ://forum/my%20file.png"),
+            asset("forum", "my file.png")
+        );
+        assert_eq!(
+            route_str("http://forum.localhost:8888/index.html"),
+            asset("forum", "index.html")
+        );
+        assert_eq!(route_str("http://ping.localhost:8888"), Ok(HappRoute::Ping));
+        assert!(route_str("http://forum/index.html").is_err());
+        assert!(route_str("http://localhost:8888/index.html").is_err());
+        assert!(route_str("https://forum.localhost/index.html").is_err());
+    }
+
+    #[test]
+    fn detects_navigation_requests() {
+        let request = |headers: &[(&str, &str)]| {
+            let mut builder = Request::builder().uri("happ://forum/");
+            for (name, value) in headers {
+                builder = builder.header(*name, *value);
+            }
+            builder.body(vec![]).unwrap()
+        };
+
+        assert!(is_navigation_request(&request(&[]), "posts/123"));
+        assert!(is_navigation_request(
+            &request(&[("Sec-Fetch-Mode", "navigate")]),
+            "posts/1.2"
+        ));
+        assert!(is_navigation_request(
+            &request(&[("Accept", "text/html,application/xhtml+xml")]),
+            "page.html"
+        ));
+        assert!(!is_navigation_request(
+            &request(&[("Accept", "*/*"), ("Sec-Fetch-Mode", "no-cors")]),
+            "assets/chunk-1234.js"
+        ));
+    }
+
+    #[test]
+    fn keeps_encoded_traversal_sequences_inside_the_ui() {
+        // Dot segments, even encoded, are removed when parsing the URL
+        assert_eq!(
+            route_str("happ://forum/../../secret"),
+            asset("forum", "secret")
+        );
+        assert_eq!(
+            route_str("happ://forum/%2e%2e/%2E%2E/secret"),
+            asset("forum", "secret")
+        );
+
+        for uri in [
+            "happ://forum/%2e%2e%2fsecret",
+            "happ://forum/..%2f..%2fsecret",
+            "happ://forum/..%5c..%5csecret",
+            "happ://forum/index.html%00.js",
+        ] {
+            assert!(
+                matches!(route_str(uri), Err(RouteError::BadRequest(_))),
+                "{uri} was not rejected"
+            );
+        }
+    }
+}
@@ -0,0 +1,78 @@
+use tauri::{
+    http::{header, HeaderName, HeaderValue, Response},
+    Runtime,
+};
+
+use crate::{happ_origin, HolochainPlugin};
+
+/// The Content-Security-Policy for the given app, with its placeholders replaced
+async fn content_security_policy<R: Runtime>(
+    holochain: &HolochainPlugin<R>,
+    app_id: &String,
+    declared_policy: Option<String>,
+) -> String {
+    let policy = holochain
+        .ui_content_security_policy
+        .policy_for_app(app_id, declared_policy);
+
+    let app_websocket_urls: Vec<String> = holochain
+        .holochain_runtime
+        .apps_websockets_auths
+        .lock()
+        .await
+        .iter()
+        .filter(|auth| auth.app_id.eq_ignore_ascii_case(app_id))
+        .flat_map(|auth| {
+            [
+                format!("ws://localhost:{}", auth.app_websocket_port),
+                format!("ws://127.0.0.1:{}", auth.app_websocket_port),
+            ]
+        })
+        .collect();
+
+    policy
+        .replace(
+            "{happ_origin}",
+            happ_origin(&holochain.ui_serving_mode, app_id).as_str(),
+        )
+        .replace(
+            "{app_websocket_urls}",
+            app_websocket_urls.join(" ").as_str(),
+        )
+}
+
+/// Adds the Content-Security-Policy for the given app and other security headers to the response for one of its assets
+///
+/// * `declared_policy` - the `contentSecurityPolicy` declared in the `happ-ui.json` of the app's UI
+pub(crate) async fn add_security_headers<R: Runtime, B>(
+    holochain: &HolochainPlugin<R>,
+    app_id: &String,
+    declared_policy: Option<String>,
+    response: &mut Response<B>,
+) {
+    let csp_header = if holochain.ui_content_security_policy.report_only {
+        header::CONTENT_SECURITY_POLICY_REPORT_ONLY
+    } else {
+        header::CONTENT_SECURITY_POLICY
+    };
+    let policy = content_security_policy(holochain, app_id, declared_policy).await;
+
+    let headers: [(HeaderName, String); 4] = [
+        (csp_header, policy),
+        (header::X_CONTENT_TYPE_OPTIONS, String::from("nosniff")),
+        (header::REFERRER_POLICY, String::from("no-referrer")),
+        (
+            HeaderName::from_static("cross-origin-opener-policy"),
+            String::from("same-origin"),
+        ),
+    ];
+
+    for (name, value) in headers {
+        match HeaderValue::from_str(value.as_str()) {
+            Ok(value) => {
+                response.headers_mut().insert(name, value);
+            }
+            Err(err) => log::error!("Invalid value for header {name} for app {app_id}: {err:?}"),
+        }
+    }
+}
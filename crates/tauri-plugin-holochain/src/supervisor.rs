@@ -0,0 +1,146 @@
+use std::collections::BTreeMap;
+
+use holochain_runtime::{AppWebsocketAuth, SupervisorEvent};
+use holochain_types::websocket::AllowedOrigins;
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Manager, Runtime, WebviewWindow};
+
+use crate::{
+    happ_origin, holochain_window, main_window_origin, HolochainPlugin, UiServingMode,
+    DEFAULT_RUNTIME_NAME,
+};
+
+/// Payload of the `holochain://conductor-crashed` and `holochain://conductor-restart-failed` events
+#[derive(Serialize, Clone, Debug)]
+struct ConductorFailure {
+    runtime: String,
+    reason: String,
+}
+
+fn allows_origin(auth: &AppWebsocketAuth, origin: &String) -> bool {
+    match &auth.allowed_origins {
+        AllowedOrigins::Any => true,
+        AllowedOrigins::Origins(origins) => origins.contains(origin),
+    }
+}
+
+/// Updates `window.__HC_LAUNCHER_ENV__` with the new token for the app the window is connected to,
+/// and dispatches the `holochain-conductor-restarted` DOM event so that the window can reconnect
+/// Each window only gets the tokens for the app websockets that accept its origin:
+/// the windows of happs only get the token for their own app, since their UIs are not trusted with the others
+///
+/// * `app_id` - the app whose UI is open in the window, `None` for the main window and the windows built by the app itself
+fn notify_window<R: Runtime>(
+    ui_serving_mode: &UiServingMode,
+    app_id: &Option<String>,
+    window: &WebviewWindow<R>,
+    app_websockets_auths: &Vec<AppWebsocketAuth>,
+) -> crate::Result<()> {
+    let window_auths: Vec<&AppWebsocketAuth> = match app_id {
+        Some(app_id) => app_websockets_auths
+            .iter()
+            .filter(|auth| {
+                auth.app_id.eq(app_id)
+                    && allows_origin(auth, &happ_origin(ui_serving_mode, &auth.app_id))
+            })
+            .collect(),
+        None => app_websockets_auths
+            .iter()
+            .filter(|auth| allows_origin(auth, &main_window_origin()))
+            .collect(),
+    };
+
+    let auths: BTreeMap<String, serde_json::Value> = window_auths
+        .into_iter()
+        .map(|auth| {
+            (
+                auth.app_id.clone(),
+                serde_json::json!({
+                    "port": auth.app_websocket_port,
+                    "token": auth.token,
+                }),
+            )
+        })
+        .collect();
+
+    window.eval(
+        format!(
+            r#"
+            (function () {{
+                const auths = {};
+                const env = window.__HC_LAUNCHER_ENV__;
+                if (env && env.INSTALLED_APP_ID && auths[env.INSTALLED_APP_ID]) {{
+                    env.APP_INTERFACE_PORT = auths[env.INSTALLED_APP_ID].port;
+                    env.APP_INTERFACE_TOKEN = auths[env.INSTALLED_APP_ID].token;
+                }}
+                window.dispatchEvent(new CustomEvent("holochain-conductor-restarted"));
+            }})();
+        "#,
+            serde_json::to_string(&auths)?
+        )
+        .as_str(),
+    )?;
+    Ok(())
+}
+
+fn handle_supervisor_event<R: Runtime>(
+    app_handle: &AppHandle<R>,
+    runtime_name: &String,
+    ui_serving_mode: &UiServingMode,
+    event: SupervisorEvent,
+) -> crate::Result<()> {
+    match event {
+        SupervisorEvent::ConductorCrashed(reason) => {
+            app_handle.emit(
+                "holochain://conductor-crashed",
+                ConductorFailure {
+                    runtime: runtime_name.clone(),
+                    reason,
+                },
+            )?;
+        }
+        SupervisorEvent::ConductorRestarted(app_websockets_auths) => {
+            for (label, window) in app_handle.webview_windows() {
+                // Windows built by the app itself belong to the default runtime
+                let (window_runtime_name, app_id) = match holochain_window(app_handle, &label) {
+                    Some(window) => (window.runtime_name, window.app_id),
+                    None => (DEFAULT_RUNTIME_NAME.to_string(), None),
+                };
+                if window_runtime_name.ne(runtime_name) {
+                    continue;
+                }
+                if let Err(err) =
+                    notify_window(ui_serving_mode, &app_id, &window, &app_websockets_auths)
+                {
+                    log::error!("Failed to notify window {label} to reconnect: {err:?}");
+                }
+            }
+            app_handle.emit("holochain://conductor-restarted", runtime_name.clone())?;
+        }
+        SupervisorEvent::RestartFailed(reason) => {
+            app_handle.emit(
+                "holochain://conductor-restart-failed",
+                ConductorFailure {
+                    runtime: runtime_name.clone(),
+                    reason,
+                },
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// Restarts the conductor of the given runtime if it crashes, emitting `holochain://conductor-crashed`
+/// and `holochain://conductor-restarted`, and notifying the open windows to reconnect
+pub(crate) fn spawn_supervisor<R: Runtime>(holochain: &HolochainPlugin<R>) {
+    let app_handle = holochain.app_handle.clone();
+    let runtime_name = holochain.runtime_name.clone();
+    let ui_serving_mode = holochain.ui_serving_mode;
+    holochain.holochain_runtime.spawn_supervisor(move |event| {
+        if let Err(err) =
+            handle_supervisor_event(&app_handle, &runtime_name, &ui_serving_mode, event)
+        {
+            log::error!("Failed to handle supervisor event: {err:?}");
+        }
+    });
+}
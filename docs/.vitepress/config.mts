@@ -32,6 +32,10 @@ export default defineConfig({
               text: "Getting to know Tauri",
               link: "/documentation/getting-to-know-tauri",
             },
+            {
+              text: "Offline-first UIs",
+              link: "/documentation/offline-first-uis",
+            },
             {
               text: "Desktop",
               items: [
@@ -1,5 +1,5 @@
 use std::path::PathBuf;
-use tauri_plugin_holochain::{HolochainExt, HolochainPluginConfig, NetworkConfig, vec_to_locked, AppBundle};
+use tauri_plugin_holochain::{HolochainExt, HolochainPluginConfig, NetworkConfig, NetworkProfile, vec_to_locked, AppBundle};
 use tauri::{AppHandle, Listener};
 
 const APP_ID: &'static str = "example";
@@ -12,11 +12,6 @@ pub fn example_happ() -> AppBundle {
 fn network_config() -> NetworkConfig {
     let mut network_config = NetworkConfig::default();
 
-    // Don't use the bootstrap service on tauri dev mode
-    if tauri::is_dev() {
-        network_config.bootstrap_url = url2::Url2::parse("http://0.0.0.0:8888");
-    }
-
     // Don't hold any slice of the DHT in mobile
     if cfg!(mobile) {
         network_config.target_arc_factor = 0;
@@ -25,6 +20,15 @@ fn network_config() -> NetworkConfig {
     network_config
 }
 
+fn network_profile() -> NetworkProfile {
+    // Don't use the bootstrap service on tauri dev mode
+    if tauri::is_dev() {
+        NetworkProfile::LanOnly
+    } else {
+        NetworkProfile::Wan
+    }
+}
+
 fn holochain_dir() -> PathBuf {
     if tauri::is_dev() {
         // if cfg!(target_os = "android") {
@@ -72,6 +76,7 @@ pub fn run() {
         .plugin(tauri_plugin_holochain::async_init(
             vec_to_locked(vec![]),
             HolochainPluginConfig::new(holochain_dir(), network_config())
+                .network_profile(network_profile())
         ))
         .setup(|app| {
             let handle = app.handle().clone();
@@ -111,7 +116,6 @@ async fn setup(handle: AppHandle) -> anyhow::Result<()> {
         .await
         .map_err(|err| tauri_plugin_holochain::Error::ConductorApiError(err))?;
 
-    // DeepKey comes preinstalled as the first app
     if installed_apps.iter().find(|app| app.installed_app_id.as_str().eq(APP_ID)).is_none() {
         handle
             .holochain()?